@@ -0,0 +1,336 @@
+/* Pocket Camera (Game Boy Camera)
+   0x0000 - 0x1FFF  ram write enable, 0x0A enables (reads always work)
+   0x2000 - 0x3FFF  rom bank, 6 bits
+   0x4000 - 0x5FFF  ram bank 0x00 - 0x0F, or 0x10 for the M64282FP sensor registers
+   Sensor registers, mirrored every 0x80 bytes. Only A000 reads back, the rest read 0x00:
+     A000         bit 0 start capture / busy, bits 1-2 passed to the sensor
+     A001         bit 7 N, bits 5-6 VH (edge direction), bits 0-4 gain
+     A002 - A003  exposure time, big endian
+     A004         bits 4-6 edge enhancement ratio, bit 3 invert, bits 0-2 voltage ref
+     A005         zero point and output reference
+     A006 - A035  4x4 dither matrix, three thresholds per pixel position
+
+   There's no real sensor, the picture comes from a grayscale image the host hands us
+   (set_image or HostInput::CameraImage), 0 is black and 255 is white. A capture runs it
+   through exposure, edge enhancement, invert and the dither matrix and stores the
+   result in ram bank 0 at 0xA100 as 16x14 2bpp tiles.
+*/
+use std::fs;
+use std::io::{Error, ErrorKind};
+
+use crate::cartridge::mapper::{banked_read, banked_write, load_ram, Cartridge, HostInput, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+pub const IMAGE_WIDTH: usize = 128;
+pub const IMAGE_HEIGHT: usize = 112;
+
+const RAM_SIZE: usize = 0x20000;
+const REGISTER_BANK: u8 = 0x10;
+const REGISTER_COUNT: usize = 0x36;
+const TILE_DATA_START: usize = 0x100;
+const UNITY_EXPOSURE: f32 = 0x0800 as f32; // exposure value that leaves the image as it is
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+pub struct Camera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_write_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    registers: [u8; REGISTER_COUNT],
+    image: Vec<u8>, // IMAGE_WIDTH * IMAGE_HEIGHT grayscale
+    capture_cycles: u32, // left until the running capture is done, 0 when idle
+}
+
+impl Camera {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Camera {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            ram_write_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers: [0; REGISTER_COUNT],
+            image: vec![0x80; IMAGE_WIDTH * IMAGE_HEIGHT],
+            capture_cycles: 0,
+        }
+    }
+
+    // a grayscale image of any size, scaled to the sensor with nearest neighbour
+    pub fn set_image(&mut self, pixels: &[u8], width: usize, height: usize) {
+        if width == 0 || height == 0 || pixels.len() < width * height {
+            return;
+        }
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let source = (y * height / IMAGE_HEIGHT) * width + x * width / IMAGE_WIDTH;
+                self.image[y * IMAGE_WIDTH + x] = pixels[source];
+            }
+        }
+    }
+
+    pub fn capturing(&self) -> bool {
+        self.capture_cycles > 0
+    }
+
+    fn exposure(&self) -> u16 {
+        u16::from_be_bytes([self.registers[2], self.registers[3]])
+    }
+
+    // in t-cycles, the sensor runs at a quarter of the cpu clock
+    fn capture_length(&self) -> u32 {
+        let n = self.registers[1] & 0x80 != 0;
+        4 * (32446 + if n { 0 } else { 512 } + 16 * self.exposure() as u32)
+    }
+
+    fn exposed_pixel(&self, x: usize, y: usize) -> f32 {
+        let x = x.min(IMAGE_WIDTH - 1);
+        let y = y.min(IMAGE_HEIGHT - 1);
+        self.image[y * IMAGE_WIDTH + x] as f32 * self.exposure() as f32 / UNITY_EXPOSURE
+    }
+
+    fn processed_pixel(&self, x: usize, y: usize) -> f32 {
+        let center = self.exposed_pixel(x, y);
+        let ratio = EDGE_RATIOS[((self.registers[4] >> 4) & 0x07) as usize];
+        let (vertical, horizontal) = match (self.registers[1] >> 5) & 0x03 {
+            0 => (false, false),
+            1 => (true, false),
+            2 => (false, true),
+            _ => (true, true),
+        };
+        let mut edge = 0.0;
+        if vertical {
+            edge += 2.0 * center - self.exposed_pixel(x, y.saturating_sub(1)) - self.exposed_pixel(x, y + 1);
+        }
+        if horizontal {
+            edge += 2.0 * center - self.exposed_pixel(x.saturating_sub(1), y) - self.exposed_pixel(x + 1, y);
+        }
+        let value = (center + ratio * edge).clamp(0.0, 255.0);
+        if self.registers[4] & 0x08 != 0 { 255.0 - value } else { value }
+    }
+
+    // darker than the first threshold is colour 3 (black), brighter than the last is 0
+    fn dithered_pixel(&self, x: usize, y: usize) -> u8 {
+        let value = self.processed_pixel(x, y) as u8;
+        let matrix = 6 + ((y % 4) * 4 + x % 4) * 3;
+        let thresholds = &self.registers[matrix..matrix + 3];
+        match thresholds.iter().position(|&threshold| value < threshold) {
+            Some(level) => 3 - level as u8,
+            None => 0,
+        }
+    }
+
+    fn finish_capture(&mut self) {
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let colour = self.dithered_pixel(x, y);
+                let tile = (y / 8) * (IMAGE_WIDTH / 8) + x / 8;
+                let offset = TILE_DATA_START + tile * 16 + (y % 8) * 2;
+                let bit = 0x80 >> (x % 8);
+                set_bit(&mut self.ram[offset], bit, colour & 0x01 != 0);
+                set_bit(&mut self.ram[offset + 1], bit, colour & 0x02 != 0);
+            }
+        }
+        self.registers[0] &= !0x01;
+    }
+}
+
+fn set_bit(byte: &mut u8, bit: u8, on: bool) {
+    if on { *byte |= bit } else { *byte &= !bit }
+}
+
+/* Reads a binary (P5) or plain (P2) PGM, the simplest grayscale format around.
+   Returns the pixels along with the width and height. */
+pub fn load_pgm(path: &str) -> Result<(Vec<u8>, usize, usize), Error> {
+    let data = fs::read(path)?;
+    let invalid = |message: &str| Error::new(ErrorKind::InvalidData, format!("{path}: {message}"));
+
+    // header is magic, width, height and max value separated by whitespace, with # comments
+    let mut fields = Vec::new();
+    let mut position = 0;
+    while fields.len() < 4 && position < data.len() {
+        match data[position] {
+            b'#' => {
+                while position < data.len() && data[position] != b'\n' {
+                    position += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() => position += 1,
+            _ => {
+                let start = position;
+                while position < data.len() && !data[position].is_ascii_whitespace() {
+                    position += 1;
+                }
+                fields.push(String::from_utf8_lossy(&data[start..position]).to_string());
+            }
+        }
+    }
+    position += 1; // the single whitespace byte before binary pixel data
+    if fields.len() < 4 {
+        return Err(invalid("truncated header"));
+    }
+    let parse = |field: &str| field.parse::<usize>().map_err(|_| invalid("bad header number"));
+    let (width, height, max) = (parse(&fields[1])?, parse(&fields[2])?, parse(&fields[3])?.max(1));
+    let scale = |value: usize| (value.min(max) * 255 / max) as u8;
+
+    let pixels: Vec<u8> = match fields[0].as_str() {
+        "P5" if max < 256 => data.get(position..).unwrap_or(&[]).iter().map(|&b| scale(b as usize)).collect(),
+        "P5" => data.get(position..).unwrap_or(&[]).chunks_exact(2)
+            .map(|pair| scale(u16::from_be_bytes([pair[0], pair[1]]) as usize)).collect(),
+        "P2" => String::from_utf8_lossy(&data[position.min(data.len())..])
+            .split_whitespace()
+            .filter_map(|value| value.parse::<usize>().ok())
+            .map(scale)
+            .collect(),
+        _ => return Err(invalid("not a PGM (P2/P5) image")),
+    };
+    if pixels.len() < width * height {
+        return Err(invalid("not enough pixel data"));
+    }
+    Ok((pixels, width, height))
+}
+
+impl Cartridge for Camera {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_write_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = value & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = value & 0x1F,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ram_bank & REGISTER_BANK != 0 {
+            return match address & 0x7F {
+                0x00 => self.registers[0] & 0x07,
+                _ => 0x00,
+            };
+        }
+        banked_read(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (address - 0xA000) as usize)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ram_bank & REGISTER_BANK != 0 {
+            let register = (address & 0x7F) as usize;
+            if register == 0 {
+                self.registers[0] = value & 0x07;
+                if value & 0x01 != 0 && !self.capturing() {
+                    self.capture_cycles = self.capture_length();
+                } else if value & 0x01 == 0 {
+                    self.capture_cycles = 0; // clearing the bit aborts the capture
+                }
+            } else if register < REGISTER_COUNT {
+                self.registers[register] = value;
+            }
+            return;
+        }
+        if self.ram_write_enabled {
+            banked_write(&mut self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (address - 0xA000) as usize, value);
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if !self.capturing() {
+            return;
+        }
+        self.capture_cycles = self.capture_cycles.saturating_sub(cycles);
+        if self.capture_cycles == 0 {
+            self.finish_capture();
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_write_enabled
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data)
+    }
+
+    fn host_input(&mut self, input: HostInput) {
+        if let HostInput::CameraImage { pixels, width, height } = input {
+            self.set_image(&pixels, width, height)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sensor registers with exposure at unity and the same three thresholds everywhere in the matrix
+    fn camera() -> Camera {
+        let mut cart = Camera::new(vec![0; 0x8000]);
+        cart.write_rom(0x4000, REGISTER_BANK);
+        cart.write_ram(0xA002, 0x08);
+        cart.write_ram(0xA003, 0x00);
+        for position in 0..16 {
+            for (i, threshold) in [0x40, 0x80, 0xC0].into_iter().enumerate() {
+                cart.write_ram(0xA006 + position * 3 + i as u16, threshold);
+            }
+        }
+        cart
+    }
+
+    #[test]
+    fn capture_dithers_into_tiles_once_done() {
+        let mut cart = camera();
+        // black on the left, white on the right
+        cart.host_input(HostInput::CameraImage { pixels: vec![0, 255], width: 2, height: 1 });
+        cart.write_ram(0xA000, 0x01);
+        assert_eq!(cart.read_ram(0xA080), 0x01);
+        cart.tick(cart.capture_length() - 1);
+        assert!(cart.capturing());
+        cart.tick(1);
+        assert_eq!(cart.read_ram(0xA000), 0x00);
+        let ram = cart.save_data().unwrap();
+        let right_half = TILE_DATA_START + 8 * 16;
+        assert_eq!((ram[TILE_DATA_START], ram[TILE_DATA_START + 1]), (0xFF, 0xFF));
+        assert_eq!((ram[right_half], ram[right_half + 1]), (0x00, 0x00));
+    }
+
+    #[test]
+    fn invert_flips_the_picture() {
+        let mut cart = camera();
+        cart.set_image(&[0], 1, 1);
+        cart.write_ram(0xA004, 0x08);
+        cart.write_ram(0xA000, 0x01);
+        cart.tick(cart.capture_length());
+        assert_eq!(cart.save_data().unwrap()[TILE_DATA_START], 0x00);
+    }
+
+    #[test]
+    fn ram_reads_always_but_writes_need_the_enable() {
+        let mut cart = Camera::new(vec![0; 0x8000]);
+        cart.write_rom(0x4000, 0x01);
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.read_ram(0xA000), 0x00);
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.read_ram(0xA000), 0x12);
+        // only the first sensor register reads back
+        cart.write_rom(0x4000, REGISTER_BANK);
+        cart.write_ram(0xA001, 0xFF);
+        assert_eq!(cart.read_ram(0xA001), 0x00);
+    }
+
+    #[test]
+    fn plain_pgm() {
+        let path = std::env::temp_dir().join(format!("emulator-camera-{}.pgm", std::process::id()));
+        fs::write(&path, "P2\n# a comment\n2 1\n15\n0 15\n").unwrap();
+        let loaded = load_pgm(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), (vec![0, 255], 2, 1));
+    }
+}
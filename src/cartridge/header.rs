@@ -0,0 +1,380 @@
+/* Cartridge header, 0x0100 - 0x014F
+   0x0100 - 0x0103  entry point
+   0x0104 - 0x0133  nintendo logo
+   0x0134 - 0x0143  title (newer carts: title 0x134-0x13E, manufacturer 0x13F-0x142, cgb flag 0x143)
+   0x0144 - 0x0145  new licensee code
+   0x0146           sgb flag
+   0x0147           cartridge type
+   0x0148           rom size
+   0x0149           ram size
+   0x014A           destination code
+   0x014B           old licensee code
+   0x014C           version
+   0x014D           header checksum
+   0x014E - 0x014F  global checksum (big endian)
+*/
+use std::fmt;
+
+pub const HEADER_END: usize = 0x150;
+
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mapper {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    BandaiTama5,
+    HuC3,
+    HuC1,
+    Unknown(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mapper: Mapper,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+    pub sensor: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> Self {
+        // (mapper, ram, battery, timer, rumble, sensor)
+        let (mapper, ram, battery, timer, rumble, sensor) = match code {
+            0x00 => (Mapper::RomOnly, false, false, false, false, false),
+            0x01 => (Mapper::Mbc1, false, false, false, false, false),
+            0x02 => (Mapper::Mbc1, true, false, false, false, false),
+            0x03 => (Mapper::Mbc1, true, true, false, false, false),
+            0x05 => (Mapper::Mbc2, false, false, false, false, false),
+            0x06 => (Mapper::Mbc2, false, true, false, false, false),
+            0x08 => (Mapper::RomOnly, true, false, false, false, false),
+            0x09 => (Mapper::RomOnly, true, true, false, false, false),
+            0x0B => (Mapper::Mmm01, false, false, false, false, false),
+            0x0C => (Mapper::Mmm01, true, false, false, false, false),
+            0x0D => (Mapper::Mmm01, true, true, false, false, false),
+            0x0F => (Mapper::Mbc3, false, true, true, false, false),
+            0x10 => (Mapper::Mbc3, true, true, true, false, false),
+            0x11 => (Mapper::Mbc3, false, false, false, false, false),
+            0x12 => (Mapper::Mbc3, true, false, false, false, false),
+            0x13 => (Mapper::Mbc3, true, true, false, false, false),
+            0x19 => (Mapper::Mbc5, false, false, false, false, false),
+            0x1A => (Mapper::Mbc5, true, false, false, false, false),
+            0x1B => (Mapper::Mbc5, true, true, false, false, false),
+            0x1C => (Mapper::Mbc5, false, false, false, true, false),
+            0x1D => (Mapper::Mbc5, true, false, false, true, false),
+            0x1E => (Mapper::Mbc5, true, true, false, true, false),
+            0x20 => (Mapper::Mbc6, true, true, false, false, false),
+            0x22 => (Mapper::Mbc7, true, true, false, true, true),
+            0xFC => (Mapper::PocketCamera, true, true, false, false, false),
+            0xFD => (Mapper::BandaiTama5, true, true, true, false, false),
+            0xFE => (Mapper::HuC3, true, true, true, false, false),
+            0xFF => (Mapper::HuC1, true, true, false, false, false),
+            _ => (Mapper::Unknown(code), false, false, false, false, false),
+        };
+        CartridgeType { code, mapper, ram, battery, timer, rumble, sensor }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CgbFlag {
+    DmgOnly,
+    CgbSupported, // bit 7 (0x80), works on both
+    CgbOnly, // bits 7 and 6 (0xC0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+    Unknown(u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub manufacturer_code: Option<String>,
+    pub cgb_flag: CgbFlag,
+    pub sgb_flag: bool,
+    pub cartridge_type: CartridgeType,
+    pub rom_size: usize, // bytes, 0 if the code is unknown
+    pub ram_size: usize, // bytes
+    pub rom_size_code: u8,
+    pub ram_size_code: u8,
+    pub destination: Destination,
+    pub old_licensee_code: u8,
+    pub new_licensee_code: Option<String>, // only used when the old code is 0x33
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+// result of checking the header against the rest of the image
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderReport {
+    pub header: CartridgeHeader,
+    pub logo_valid: bool,
+    pub computed_header_checksum: u8,
+    pub computed_global_checksum: u16,
+}
+
+impl HeaderReport {
+    pub fn header_checksum_valid(&self) -> bool {
+        self.computed_header_checksum == self.header.header_checksum
+    }
+
+    pub fn global_checksum_valid(&self) -> bool {
+        self.computed_global_checksum == self.header.global_checksum
+    }
+
+    // the boot rom refuses to start a cart with a bad logo or header checksum, the global checksum isn't checked
+    pub fn bootable(&self) -> bool {
+        self.logo_valid && self.header_checksum_valid()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderError {
+    TooShort(usize),
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeaderError::TooShort(len) => write!(f, "rom is {len} bytes, too short to hold a header"),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+pub fn parse(rom: &[u8]) -> Result<CartridgeHeader, HeaderError> {
+    if rom.len() < HEADER_END {
+        return Err(HeaderError::TooShort(rom.len()));
+    }
+
+    // the cgb only looks at bit 7, bit 6 is set by games that won't run on a DMG
+    let cgb_flag = match rom[0x143] {
+        flag if flag & 0x80 == 0 => CgbFlag::DmgOnly,
+        flag if flag & 0x40 != 0 => CgbFlag::CgbOnly,
+        _ => CgbFlag::CgbSupported,
+    };
+    // carts made after the cgb shrank the title to fit a manufacturer code and the cgb flag
+    let manufacturer = &rom[0x13F..0x143];
+    let has_manufacturer = cgb_flag != CgbFlag::DmgOnly
+        && manufacturer.iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit());
+    let (title, manufacturer_code) = if has_manufacturer {
+        (ascii_field(&rom[0x134..0x13F]), Some(ascii_field(manufacturer)))
+    } else if cgb_flag != CgbFlag::DmgOnly {
+        (ascii_field(&rom[0x134..0x143]), None)
+    } else {
+        (ascii_field(&rom[0x134..0x144]), None)
+    };
+
+    let old_licensee_code = rom[0x14B];
+    let new_licensee_code = if old_licensee_code == 0x33 {
+        Some(ascii_field(&rom[0x144..0x146]))
+    } else {
+        None
+    };
+
+    Ok(CartridgeHeader {
+        title,
+        manufacturer_code,
+        cgb_flag,
+        sgb_flag: rom[0x146] == 0x03,
+        cartridge_type: CartridgeType::from_code(rom[0x147]),
+        rom_size: rom_size(rom[0x148]),
+        ram_size: ram_size(rom[0x149]),
+        rom_size_code: rom[0x148],
+        ram_size_code: rom[0x149],
+        destination: match rom[0x14A] {
+            0x00 => Destination::Japan,
+            0x01 => Destination::Overseas,
+            code => Destination::Unknown(code),
+        },
+        old_licensee_code,
+        new_licensee_code,
+        version: rom[0x14C],
+        header_checksum: rom[0x14D],
+        global_checksum: u16::from_be_bytes([rom[0x14E], rom[0x14F]]),
+    })
+}
+
+pub fn validate(rom: &[u8]) -> Result<HeaderReport, HeaderError> {
+    let header = parse(rom)?;
+    Ok(HeaderReport {
+        header,
+        logo_valid: rom[0x104..0x134] == NINTENDO_LOGO,
+        computed_header_checksum: header_checksum(rom),
+        computed_global_checksum: global_checksum(rom),
+    })
+}
+
+// x = x - byte - 1 over 0x134 - 0x14C, same as the boot rom
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[0x134..=0x14C]
+        .iter()
+        .fold(0u8, |x, &byte| x.wrapping_sub(byte).wrapping_sub(1))
+}
+
+// sum of every byte in the rom except the two checksum bytes themselves
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 0x14E && *i != 0x14F)
+        .fold(0u16, |sum, (_, &byte)| sum.wrapping_add(byte as u16))
+}
+
+// 32 KiB shifted by the code, plus the three odd sizes some docs list
+pub fn rom_size(code: u8) -> usize {
+    match code {
+        0x00..=0x08 => 0x8000 << code,
+        0x52 => 72 * 0x4000,
+        0x53 => 80 * 0x4000,
+        0x54 => 96 * 0x4000,
+        _ => 0,
+    }
+}
+
+pub fn ram_size(code: u8) -> usize {
+    match code {
+        0x01 => 0x800, // unofficial, seen on a few homebrew carts
+        0x02 => 0x2000,
+        0x03 => 0x8000,
+        0x04 => 0x20000,
+        0x05 => 0x10000,
+        _ => 0,
+    }
+}
+
+fn ascii_field(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|&&b| b != 0)
+        .filter(|b| b.is_ascii_graphic() || **b == b' ')
+        .map(|&b| b as char)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+impl fmt::Display for HeaderReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = &self.header;
+        let cart = &header.cartridge_type;
+        let ok = |valid: bool| if valid { "ok" } else { "BAD" };
+        writeln!(f, "title:            {}", header.title)?;
+        if let Some(code) = &header.manufacturer_code {
+            writeln!(f, "manufacturer:     {code}")?;
+        }
+        writeln!(f, "cartridge type:   0x{:02X} {:?}{}{}{}{}{}", cart.code, cart.mapper,
+            if cart.ram { " +RAM" } else { "" },
+            if cart.battery { " +BATTERY" } else { "" },
+            if cart.timer { " +TIMER" } else { "" },
+            if cart.rumble { " +RUMBLE" } else { "" },
+            if cart.sensor { " +SENSOR" } else { "" })?;
+        writeln!(f, "rom size:         {} KiB (code 0x{:02X})", header.rom_size / 1024, header.rom_size_code)?;
+        writeln!(f, "ram size:         {} KiB (code 0x{:02X})", header.ram_size / 1024, header.ram_size_code)?;
+        writeln!(f, "cgb:              {:?}", header.cgb_flag)?;
+        writeln!(f, "sgb:              {}", header.sgb_flag)?;
+        writeln!(f, "destination:      {:?}", header.destination)?;
+        match &header.new_licensee_code {
+            Some(code) => writeln!(f, "licensee:         {code} (new)")?,
+            None => writeln!(f, "licensee:         0x{:02X} (old)", header.old_licensee_code)?,
+        }
+        writeln!(f, "version:          {}", header.version)?;
+        writeln!(f, "logo:             {}", ok(self.logo_valid))?;
+        writeln!(f, "header checksum:  0x{:02X} (computed 0x{:02X}) {}", header.header_checksum,
+            self.computed_header_checksum, ok(self.header_checksum_valid()))?;
+        write!(f, "global checksum:  0x{:04X} (computed 0x{:04X}) {}", header.global_checksum,
+            self.computed_global_checksum, ok(self.global_checksum_valid()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an MBC3 cart with a cgb title, both checksums filled in
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x134..0x143].copy_from_slice(b"POKEMON_SLVAAXE");
+        rom[0x143] = 0x80;
+        rom[0x144..0x146].copy_from_slice(b"01");
+        rom[0x147] = 0x10;
+        rom[0x148] = 0x00;
+        rom[0x149] = 0x03;
+        rom[0x14B] = 0x33;
+        rom[0x14D] = header_checksum(&rom);
+        let sum = global_checksum(&rom);
+        rom[0x14E..0x150].copy_from_slice(&sum.to_be_bytes());
+        rom
+    }
+
+    #[test]
+    fn parses_the_fields() {
+        let header = parse(&rom()).unwrap();
+        assert_eq!(header.title, "POKEMON_SLV");
+        assert_eq!(header.manufacturer_code.as_deref(), Some("AAXE"));
+        assert_eq!(header.cgb_flag, CgbFlag::CgbSupported);
+        assert_eq!(header.new_licensee_code.as_deref(), Some("01"));
+        let cart = header.cartridge_type;
+        assert_eq!((cart.mapper, cart.ram, cart.battery, cart.timer), (Mapper::Mbc3, true, true, true));
+        assert_eq!((header.rom_size, header.ram_size), (0x8000, 0x8000));
+        assert_eq!(header.destination, Destination::Japan);
+    }
+
+    #[test]
+    fn the_cgb_flag_goes_by_bits_7_and_6() {
+        let mut rom = rom();
+        for (flag, expected) in [(0x84, CgbFlag::CgbSupported), (0x88, CgbFlag::CgbSupported),
+                                 (0xC0, CgbFlag::CgbOnly), (0xC8, CgbFlag::CgbOnly), (0x40, CgbFlag::DmgOnly)] {
+            rom[0x143] = flag;
+            assert_eq!(parse(&rom).unwrap().cgb_flag, expected);
+        }
+    }
+
+    #[test]
+    fn a_dmg_title_runs_the_full_sixteen_bytes() {
+        let mut rom = rom();
+        rom[0x143] = b'S';
+        assert_eq!(parse(&rom).unwrap().title, "POKEMON_SLVAAXES");
+    }
+
+    #[test]
+    fn validation_checks_the_logo_and_both_checksums() {
+        let report = validate(&rom()).unwrap();
+        assert!(report.logo_valid && report.header_checksum_valid() && report.global_checksum_valid());
+        assert!(report.bootable());
+
+        let mut rom = rom();
+        rom[0x7FFF] = 1; // only the global checksum covers this, and the boot rom doesn't check it
+        let report = validate(&rom).unwrap();
+        assert!(!report.global_checksum_valid() && report.bootable());
+        rom[0x14C] = 1;
+        assert!(!validate(&rom).unwrap().bootable());
+        let mut rom = self::rom();
+        rom[0x104] = 0;
+        assert!(!validate(&rom).unwrap().logo_valid);
+    }
+
+    #[test]
+    fn too_short_for_a_header() {
+        assert_eq!(parse(&[0; 0x14F]), Err(HeaderError::TooShort(0x14F)));
+    }
+}
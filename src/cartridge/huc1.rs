@@ -0,0 +1,141 @@
+/* HuC1 (Hudson)
+   0x0000 - 0x1FFF  0x0E switches 0xA000 - 0xBFFF to the infrared port, anything else back to ram
+   0x2000 - 0x3FFF  rom bank, 6 bits
+   0x4000 - 0x5FFF  ram bank, 2 bits
+   In IR mode reads give 0xC1 while light is seen and 0xC0 otherwise, bit 0 of a write drives the LED.
+   The ram has no enable, but games still write 0x0A before using it and something else
+   once they're done, like they would on MBC1.
+*/
+use crate::cartridge::mapper::{banked_read, banked_write, load_ram, Cartridge, CartridgeEvent, HostInput, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+pub struct HuC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ir_mode: bool,
+    ram_enabled: bool, // 0x0A was the last write to 0x0000 - 0x1FFF, only used to tell when a save is done
+    rom_bank: u8,
+    ram_bank: u8,
+    ir_led: bool,
+    ir_light: bool, // light seen by the receiver, set by the host
+    events: Vec<CartridgeEvent>,
+}
+
+impl HuC1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        HuC1 {
+            rom,
+            ram: vec![0; ram_size],
+            ir_mode: false,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            ir_led: false,
+            ir_light: false,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl Cartridge for HuC1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ir_mode = value == 0x0E;
+                self.ram_enabled = value & 0x0F == 0x0A;
+            }
+            0x2000..=0x3FFF => self.rom_bank = value & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = value & 0x03,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ir_mode {
+            return 0xC0 | self.ir_light as u8;
+        }
+        banked_read(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (address - 0xA000) as usize)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ir_mode {
+            let led = value & 0x01 != 0;
+            if led != self.ir_led {
+                self.ir_led = led;
+                self.events.push(CartridgeEvent::Infrared(led));
+            }
+            return;
+        }
+        banked_write(&mut self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (address - 0xA000) as usize, value)
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.ram.is_empty() { None } else { Some(self.ram.clone()) }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data)
+    }
+
+    fn take_events(&mut self) -> Vec<CartridgeEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn host_input(&mut self, input: HostInput) {
+        if let HostInput::Infrared(light) = input {
+            self.ir_light = light
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::mapper::numbered_banks;
+
+    #[test]
+    fn banks_and_ram_are_always_on() {
+        let mut cart = HuC1::new(numbered_banks(64), 0x8000);
+        cart.write_rom(0x2000, 0x3F);
+        assert_eq!(cart.read_rom(0x4000), 0x3F);
+        cart.write_rom(0x4000, 0x03);
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.save_data().unwrap()[3 * RAM_BANK_SIZE], 0x12);
+    }
+
+    #[test]
+    fn ram_enabled_follows_the_mbc1_habit() {
+        let mut cart = HuC1::new(numbered_banks(4), 0x2000);
+        cart.write_rom(0x0000, 0x0A);
+        assert!(cart.ram_enabled());
+        cart.write_rom(0x0000, 0x00);
+        assert!(!cart.ram_enabled());
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.read_ram(0xA000), 0x12);
+    }
+
+    #[test]
+    fn infrared_mode() {
+        let mut cart = HuC1::new(numbered_banks(4), 0x2000);
+        cart.write_rom(0x0000, 0x0E);
+        assert_eq!(cart.read_ram(0xA000), 0xC0);
+        cart.host_input(HostInput::Infrared(true));
+        assert_eq!(cart.read_ram(0xA000), 0xC1);
+        cart.write_ram(0xA000, 0x01);
+        cart.write_ram(0xA000, 0x01);
+        assert_eq!(cart.take_events(), vec![CartridgeEvent::Infrared(true)]);
+        // the led write didn't reach the ram
+        cart.write_rom(0x0000, 0x00);
+        assert_eq!(cart.read_ram(0xA000), 0x00);
+    }
+}
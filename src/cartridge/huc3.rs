@@ -0,0 +1,290 @@
+/* HuC3 (Hudson)
+   0x0000 - 0x1FFF  mode for 0xA000 - 0xBFFF:
+                      0x0  ram, read only
+                      0xA  ram, read/write
+                      0xB  rtc command (write)
+                      0xC  rtc response (read)
+                      0xD  rtc semaphore, reads 1 when the rtc is ready
+                      0xE  infrared, same as HuC1
+   0x2000 - 0x3FFF  rom bank, 7 bits
+   0x4000 - 0x5FFF  ram bank, 2 bits
+
+   RTC commands are one byte, command in the upper nibble and an argument nibble below.
+   They go through a 256 nibble register file picked by an access address:
+     1  read the nibble at the address into the response, then address + 1
+     2  write the argument to the address
+     3  write the argument to the address, then address + 1
+     4  set the low nibble of the address
+     5  set the high nibble of the address
+     6  extended: argument 0xE plays the tone picked by register 0x27 on the speaker
+   Registers 0x00 - 0x02 are the minute of the day and 0x03 - 0x05 the day counter (12 bits each).
+   0x58 - 0x5A and 0x5B - 0x5D hold the alarm time and 0x5F bit 0 arms it.
+*/
+use crate::cartridge::mapper::{banked_read, banked_write, load_ram, Cartridge, CartridgeEvent, HostInput, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::cartridge::rtc::{ClockState, HuC3Clock, unix_now, CYCLES_PER_SECOND};
+
+const MINUTES_PER_DAY: u16 = 1440;
+const CYCLES_PER_MINUTE: u64 = CYCLES_PER_SECOND as u64 * 60;
+
+pub struct HuC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mode: u8,
+    rom_bank: u8,
+    ram_bank: u8,
+    registers: [u8; 0x100], // nibbles that aren't the clock or the alarm
+    access_address: u8,
+    response: u8,
+    minutes: u16,
+    days: u16,
+    alarm_minutes: u16,
+    alarm_days: u16,
+    alarm_enabled: bool,
+    cycles: u64, // progress towards the next minute
+    ir_led: bool,
+    ir_light: bool,
+    events: Vec<CartridgeEvent>,
+}
+
+impl HuC3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        HuC3 {
+            rom,
+            ram: vec![0; ram_size],
+            mode: 0,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers: [0; 0x100],
+            access_address: 0,
+            response: 0,
+            minutes: 0,
+            days: 0,
+            alarm_minutes: 0,
+            alarm_days: 0,
+            alarm_enabled: false,
+            cycles: 0,
+            ir_led: false,
+            ir_light: false,
+            events: Vec::new(),
+        }
+    }
+
+    fn read_register(&self, address: u8) -> u8 {
+        match address {
+            0x00..=0x02 => nibble(self.minutes, address),
+            0x03..=0x05 => nibble(self.days, address - 0x03),
+            0x58..=0x5A => nibble(self.alarm_minutes, address - 0x58),
+            0x5B..=0x5D => nibble(self.alarm_days, address - 0x5B),
+            0x5F => self.alarm_enabled as u8,
+            _ => self.registers[address as usize],
+        }
+    }
+
+    fn write_register(&mut self, address: u8, value: u8) {
+        let value = value & 0x0F;
+        match address {
+            0x00..=0x02 => set_nibble(&mut self.minutes, address, value),
+            0x03..=0x05 => set_nibble(&mut self.days, address - 0x03, value),
+            0x58..=0x5A => set_nibble(&mut self.alarm_minutes, address - 0x58, value),
+            0x5B..=0x5D => set_nibble(&mut self.alarm_days, address - 0x5B, value),
+            0x5F => self.alarm_enabled = value & 0x01 != 0,
+            _ => self.registers[address as usize] = value,
+        }
+    }
+
+    fn command(&mut self, value: u8) {
+        let argument = value & 0x0F;
+        match value >> 4 {
+            0x1 => {
+                self.response = self.read_register(self.access_address);
+                self.access_address = self.access_address.wrapping_add(1);
+            }
+            0x2 => self.write_register(self.access_address, argument),
+            0x3 => {
+                self.write_register(self.access_address, argument);
+                self.access_address = self.access_address.wrapping_add(1);
+            }
+            0x4 => self.access_address = (self.access_address & 0xF0) | argument,
+            0x5 => self.access_address = (self.access_address & 0x0F) | (argument << 4),
+            0x6 if argument == 0xE => self.events.push(CartridgeEvent::Tone(self.registers[0x27])),
+            _ => {}
+        }
+    }
+
+    fn advance_minutes(&mut self, minutes: u64) {
+        for _ in 0..minutes.min(MINUTES_PER_DAY as u64 * 0x1000) {
+            self.minutes += 1;
+            if self.minutes >= MINUTES_PER_DAY {
+                self.minutes = 0;
+                self.days = (self.days + 1) & 0x0FFF;
+            }
+            if self.alarm_enabled && self.minutes == self.alarm_minutes && self.days == self.alarm_days {
+                self.events.push(CartridgeEvent::Alarm);
+                self.events.push(CartridgeEvent::Tone(self.registers[0x27]));
+            }
+        }
+    }
+}
+
+fn nibble(value: u16, index: u8) -> u8 {
+    ((value >> (index * 4)) & 0x0F) as u8
+}
+
+fn set_nibble(value: &mut u16, index: u8, nibble: u8) {
+    *value = (*value & !(0x0F << (index * 4))) | ((nibble as u16) << (index * 4));
+}
+
+impl Cartridge for HuC3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.mode = value & 0x0F,
+            0x2000..=0x3FFF => self.rom_bank = value & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank = value & 0x03,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.mode {
+            0x0 | 0xA => banked_read(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (address - 0xA000) as usize),
+            0xC => 0x80 | self.response,
+            0xD => 0x01, // commands finish instantly, the rtc is always ready
+            0xE => 0xC0 | self.ir_light as u8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        match self.mode {
+            0xA => banked_write(&mut self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (address - 0xA000) as usize, value),
+            0xB => self.command(value),
+            0xE => {
+                let led = value & 0x01 != 0;
+                if led != self.ir_led {
+                    self.ir_led = led;
+                    self.events.push(CartridgeEvent::Infrared(led));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+        if self.cycles >= CYCLES_PER_MINUTE {
+            let minutes = self.cycles / CYCLES_PER_MINUTE;
+            self.cycles %= CYCLES_PER_MINUTE;
+            self.advance_minutes(minutes);
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.mode == 0xA
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.ram.is_empty() { None } else { Some(self.ram.clone()) }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data)
+    }
+
+    fn rtc_state(&self) -> Option<ClockState> {
+        Some(ClockState::HuC3(HuC3Clock {
+            minutes: self.minutes,
+            days: self.days,
+            alarm_minutes: self.alarm_minutes,
+            alarm_days: self.alarm_days,
+            alarm_enabled: self.alarm_enabled,
+            timestamp: unix_now(),
+        }))
+    }
+
+    fn load_rtc_state(&mut self, state: &ClockState, elapsed_seconds: u64) {
+        let ClockState::HuC3(clock) = state else {
+            return;
+        };
+        self.minutes = clock.minutes % MINUTES_PER_DAY;
+        self.days = clock.days & 0x0FFF;
+        self.alarm_minutes = clock.alarm_minutes;
+        self.alarm_days = clock.alarm_days;
+        self.alarm_enabled = clock.alarm_enabled;
+        self.cycles = 0;
+        self.advance_minutes(elapsed_seconds / 60);
+    }
+
+    fn take_events(&mut self) -> Vec<CartridgeEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn host_input(&mut self, input: HostInput) {
+        if let HostInput::Infrared(light) = input {
+            self.ir_light = light
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(cart: &mut HuC3, commands: &[u8]) {
+        cart.write_rom(0x0000, 0x0B);
+        for &command in commands {
+            cart.write_ram(0xA000, command);
+        }
+    }
+
+    // reads `count` nibbles starting at `address`
+    fn read(cart: &mut HuC3, address: u8, count: usize) -> Vec<u8> {
+        command(cart, &[0x40 | (address & 0x0F), 0x50 | (address >> 4)]);
+        (0..count)
+            .map(|_| {
+                command(cart, &[0x10]);
+                cart.write_rom(0x0000, 0x0C);
+                cart.read_ram(0xA000) & 0x0F
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_clock_counts_minutes_and_days() {
+        let mut cart = HuC3::new(vec![0; 0x8000], 0x2000);
+        // 23:59 on day 0x123
+        command(&mut cart, &[0x40, 0x50, 0x3F, 0x39, 0x35, 0x33, 0x32, 0x31]);
+        cart.tick(CYCLES_PER_MINUTE as u32);
+        assert_eq!(read(&mut cart, 0x00, 6), vec![0, 0, 0, 4, 2, 1]);
+    }
+
+    #[test]
+    fn the_alarm_goes_off_and_plays_the_tone() {
+        let mut cart = HuC3::new(vec![0; 0x8000], 0x2000);
+        // alarm at minute 1 of day 0, armed, tone 3
+        command(&mut cart, &[0x48, 0x55, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x4F, 0x21, 0x47, 0x52, 0x23]);
+        cart.tick(CYCLES_PER_MINUTE as u32);
+        assert_eq!(cart.take_events(), vec![CartridgeEvent::Alarm, CartridgeEvent::Tone(3)]);
+        command(&mut cart, &[0x6E]);
+        assert_eq!(cart.take_events(), vec![CartridgeEvent::Tone(3)]);
+    }
+
+    #[test]
+    fn semaphore_and_ram_modes() {
+        let mut cart = HuC3::new(vec![0; 0x8000], 0x2000);
+        cart.write_rom(0x0000, 0x0D);
+        assert_eq!(cart.read_ram(0xA000), 0x01);
+        cart.write_rom(0x0000, 0x00);
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.read_ram(0xA000), 0x00);
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.read_ram(0xA000), 0x12);
+    }
+}
@@ -0,0 +1,208 @@
+/* Everything the memory bus needs from a cartridge.
+   0x0000 - 0x7FFF reads come from rom, writes there go to the mapper (MBC) registers.
+   0xA000 - 0xBFFF is external ram, or whatever else the mapper puts there.
+*/
+use crate::cartridge::camera::Camera;
+use crate::cartridge::header::{self, HeaderReport, Mapper};
+use crate::cartridge::huc1::HuC1;
+use crate::cartridge::huc3::HuC3;
+use crate::cartridge::mbc1::Mbc1;
+use crate::cartridge::mbc2::Mbc2;
+use crate::cartridge::mbc3::Mbc3;
+use crate::cartridge::mbc5::Mbc5;
+use crate::cartridge::mbc6::Mbc6;
+use crate::cartridge::mbc7::Mbc7;
+use crate::cartridge::mmm01::Mmm01;
+use crate::cartridge::rom_only::RomOnly;
+use crate::cartridge::rtc::ClockState;
+use crate::cartridge::tama5::Tama5;
+use crate::cartridge::unlicensed::{self, UnlicensedMapper};
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/* Things a cart does that the host should hear about (motors, lights, speakers).
+   They queue up until the host drains them with take_events */
+#[derive(Debug, Clone, PartialEq)]
+pub enum CartridgeEvent {
+    Rumble(bool), // motor switched on or off
+    Infrared(bool), // IR LED switched on or off
+    Tone(u8), // HuC3 speaker played a tone
+    Alarm, // HuC3 alarm went off
+}
+
+// input from the host for carts with their own sensors
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostInput {
+    Tilt { x: f32, y: f32 }, // in g, positive x is right and positive y is down
+    Infrared(bool), // light seen by the IR receiver
+    CameraImage { pixels: Vec<u8>, width: usize, height: usize }, // grayscale, 0 is black
+}
+
+pub trait Cartridge {
+    fn read_rom(&self, address: u16) -> u8;
+    fn write_rom(&mut self, address: u16, value: u8);
+    fn read_ram(&self, address: u16) -> u8;
+    fn write_ram(&mut self, address: u16, value: u8);
+
+    // contents of the battery backed memory, None when the cart has nothing to save
+    fn save_data(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn load_save_data(&mut self, _data: &[u8]) {}
+
+    // whether the game has switched the save memory on, games switch it off again once they're done with it
+    fn ram_enabled(&self) -> bool {
+        false
+    }
+
+    // called with the t-cycles that passed, for carts with something that runs on its own (clocks, timers)
+    fn tick(&mut self, _cycles: u32) {}
+
+    // clock state for carts with an rtc, None otherwise
+    fn rtc_state(&self) -> Option<ClockState> {
+        None
+    }
+
+    // elapsed_seconds is the host time since the state was saved, pass 0 to only count emulated time
+    fn load_rtc_state(&mut self, _state: &ClockState, _elapsed_seconds: u64) {}
+
+    fn take_events(&mut self) -> Vec<CartridgeEvent> {
+        Vec::new()
+    }
+
+    // carts without the matching sensor ignore the input
+    fn host_input(&mut self, _input: HostInput) {}
+}
+
+// pick the mapper from the header, anything we can't read runs as a plain 32 KiB cart
+pub fn load(rom: Vec<u8>) -> Box<dyn Cartridge> {
+    load_with_override(rom, None)
+}
+
+// same as load, but the host can force one of the unlicensed mappers the header can't describe
+pub fn load_with_override(rom: Vec<u8>, mapper: Option<UnlicensedMapper>) -> Box<dyn Cartridge> {
+    if let Some(mapper) = mapper {
+        return unlicensed::load(rom, mapper);
+    }
+    // MMM01 boots from the end of the image, so that's where its header is
+    if let Some(menu) = rom.len().checked_sub(0x8000).and_then(|start| header::parse(&rom[start..]).ok()) {
+        if menu.cartridge_type.mapper == Mapper::Mmm01 {
+            return Box::new(Mmm01::new(rom, menu.ram_size));
+        }
+    }
+    let report = header::validate(&rom).ok();
+    // the strings the unlicensed carts are recognised by could turn up in any rom, so they're
+    // only looked for when the header can't be describing the cart
+    if !report.as_ref().is_some_and(|report| describes_cart(report, rom.len())) {
+        if let Some(mapper) = unlicensed::detect(&rom) {
+            return unlicensed::load(rom, mapper);
+        }
+    }
+    let Some(header) = report.map(|report| report.header) else {
+        return Box::new(RomOnly::new(rom, 0));
+    };
+    let ram_size = header.ram_size;
+    match header.cartridge_type.mapper {
+        Mapper::RomOnly => Box::new(RomOnly::new(rom, ram_size)),
+        Mapper::Mbc1 => Box::new(Mbc1::new(rom, ram_size)),
+        Mapper::Mbc2 => Box::new(Mbc2::new(rom)),
+        Mapper::Mmm01 => Box::new(Mmm01::new(rom, ram_size)),
+        Mapper::Mbc3 => Box::new(Mbc3::new(rom, ram_size, header.cartridge_type.timer)),
+        Mapper::Mbc5 => Box::new(Mbc5::new(rom, ram_size, header.cartridge_type.rumble)),
+        Mapper::Mbc6 => Box::new(Mbc6::new(rom)),
+        Mapper::Mbc7 => Box::new(Mbc7::new(rom)),
+        Mapper::PocketCamera => Box::new(Camera::new(rom)),
+        Mapper::HuC1 => Box::new(HuC1::new(rom, ram_size)),
+        Mapper::HuC3 => Box::new(HuC3::new(rom, ram_size)),
+        Mapper::BandaiTama5 => Box::new(Tama5::new(rom)),
+        mapper => {
+            eprintln!("unsupported mapper {:?}, running as rom only", mapper);
+            Box::new(RomOnly::new(rom, ram_size))
+        }
+    }
+}
+
+// a header that won't boot, names a mapper we don't know or claims no mapper on an image too big
+// to fit without one (which is what the unlicensed carts have) isn't the real one
+fn describes_cart(report: &HeaderReport, rom_len: usize) -> bool {
+    match report.header.cartridge_type.mapper {
+        Mapper::Unknown(_) => false,
+        Mapper::RomOnly if rom_len > 0x8000 => false,
+        _ => report.bootable(),
+    }
+}
+
+// byte `offset` into `bank`, wrapping the bank number to the size of the image like the unconnected address lines do
+pub fn banked_read(data: &[u8], bank_size: usize, bank: usize, offset: usize) -> u8 {
+    if data.is_empty() {
+        return 0xFF;
+    }
+    let banks = (data.len() / bank_size).max(1);
+    data[((bank % banks) * bank_size + offset) % data.len()]
+}
+
+// copy a save into ram, ignoring anything past the end so saves from other emulators with extra data still load
+pub fn load_ram(ram: &mut [u8], data: &[u8]) {
+    let len = ram.len().min(data.len());
+    ram[..len].copy_from_slice(&data[..len]);
+}
+
+pub fn banked_write(data: &mut [u8], bank_size: usize, bank: usize, offset: usize, value: u8) {
+    if data.is_empty() {
+        return;
+    }
+    let banks = (data.len() / bank_size).max(1);
+    let len = data.len();
+    data[((bank % banks) * bank_size + offset) % len] = value;
+}
+
+// a rom where every byte holds the number of its bank, for the mapper tests
+#[cfg(test)]
+pub fn numbered_banks(banks: usize) -> Vec<u8> {
+    (0..banks * ROM_BANK_SIZE).map(|i| (i / ROM_BANK_SIZE) as u8).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::header::NINTENDO_LOGO;
+
+    // two 32 KiB halves holding 0 and 1, with a Wisdom Tree string and a bootable header of cart_type
+    fn rom(cart_type: u8) -> Vec<u8> {
+        let mut rom: Vec<u8> = (0..0x10000).map(|i| (i / 0x8000) as u8).collect();
+        rom[0x200..0x20B].copy_from_slice(b"WISDOM TREE");
+        rom[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x147] = cart_type;
+        rom[0x148] = 0x01;
+        rom[0x14D] = header::header_checksum(&rom);
+        rom
+    }
+
+    // Wisdom Tree switches all 32 KiB on a write to the address, nothing else reacts to this one
+    fn is_wisdom_tree(mut cart: Box<dyn Cartridge>) -> bool {
+        cart.write_rom(0x0001, 0x00);
+        cart.read_rom(0x0000) == 1
+    }
+
+    #[test]
+    fn a_good_header_wins_over_the_strings() {
+        assert!(!is_wisdom_tree(load(rom(0x01))));
+    }
+
+    #[test]
+    fn strings_are_used_when_the_header_cant_be_right() {
+        // rom only, but twice too big for that
+        assert!(is_wisdom_tree(load(rom(0x00))));
+        let mut bad_checksum = rom(0x01);
+        bad_checksum[0x14D] ^= 0xFF;
+        assert!(is_wisdom_tree(load(bad_checksum)));
+        assert!(is_wisdom_tree(load(rom(0x42)))); // unknown mapper
+    }
+
+    #[test]
+    fn the_host_can_force_an_unlicensed_mapper() {
+        assert!(is_wisdom_tree(load_with_override(rom(0x01), Some(UnlicensedMapper::WisdomTree))));
+    }
+}
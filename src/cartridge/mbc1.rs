@@ -0,0 +1,189 @@
+/* MBC1
+   0x0000 - 0x1FFF  ram enable, 0x0A in the low nibble enables
+   0x2000 - 0x3FFF  bank 1 register, low 5 bits of the rom bank (0 reads as 1)
+   0x4000 - 0x5FFF  bank 2 register, 2 bits, upper rom bank bits or the ram bank
+   0x6000 - 0x7FFF  mode select, in mode 1 bank 2 also applies to 0x0000 - 0x3FFF and ram
+
+   MBC1M multicarts wire bank 2 to rom address bits 4-5 instead of 5-6, so bank 1 only
+   contributes its low 4 bits. Each sub game then gets its own 256 KiB.
+*/
+use crate::cartridge::header::NINTENDO_LOGO;
+use crate::cartridge::mapper::{banked_read, banked_write, load_ram, Cartridge, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+pub struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    bank1: u8,
+    bank2: u8,
+    mode: bool,
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        let multicart = is_multicart(&rom);
+        Mbc1 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: false,
+            multicart,
+        }
+    }
+
+    pub fn is_multicart(&self) -> bool {
+        self.multicart
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    fn low_bank(&self) -> usize {
+        if self.mode {
+            (self.bank2 << self.bank2_shift()) as usize
+        } else {
+            0
+        }
+    }
+
+    fn high_bank(&self) -> usize {
+        let bank1 = if self.multicart { self.bank1 & 0x0F } else { self.bank1 };
+        ((self.bank2 << self.bank2_shift()) | bank1) as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.mode { self.bank2 as usize } else { 0 }
+    }
+}
+
+/* MBC1M carts are 8 Mbit and have a second copy of the nintendo logo in bank 0x10,
+   the header of the first sub game. No normal 1 MiB MBC1 game has one there. */
+fn is_multicart(rom: &[u8]) -> bool {
+    if rom.len() != 0x100000 {
+        return false;
+    }
+    let logo = 0x10 * ROM_BANK_SIZE + 0x104;
+    rom[logo..logo + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+}
+
+impl Cartridge for Mbc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, self.low_bank(), address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.high_bank(), (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                // the zero check only looks at the 5 bit register, so bank 0x20 still maps to 0x21
+                self.bank1 = value & 0x1F;
+                if self.bank1 == 0 {
+                    self.bank1 = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            _ => self.mode = value & 0x01 != 0,
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        banked_read(&self.ram, RAM_BANK_SIZE, self.ram_bank(), (address - 0xA000) as usize)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        let bank = self.ram_bank();
+        banked_write(&mut self.ram, RAM_BANK_SIZE, bank, (address - 0xA000) as usize, value)
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.ram.is_empty() { None } else { Some(self.ram.clone()) }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::mapper::numbered_banks;
+
+    #[test]
+    fn bank_1_can_not_be_zero() {
+        let mut cart = Mbc1::new(numbered_banks(128), 0);
+        assert_eq!(cart.read_rom(0x4000), 1);
+        cart.write_rom(0x2000, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 1);
+        cart.write_rom(0x2000, 0x1F);
+        assert_eq!(cart.read_rom(0x7FFF), 0x1F);
+        // only the 5 bit register is checked, so 0x20 turns into 0x21
+        cart.write_rom(0x4000, 0x01);
+        cart.write_rom(0x2000, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 0x21);
+    }
+
+    #[test]
+    fn mode_1_applies_bank_2_to_the_low_area_and_ram() {
+        let mut cart = Mbc1::new(numbered_banks(128), 0x8000);
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_rom(0x4000, 0x02);
+        assert_eq!(cart.read_rom(0x0000), 0x00);
+        cart.write_ram(0xA000, 0x11);
+        cart.write_rom(0x6000, 0x01);
+        assert_eq!(cart.read_rom(0x0000), 0x40);
+        assert_eq!(cart.read_rom(0x4000), 0x41);
+        assert_eq!(cart.read_ram(0xA000), 0x00);
+        cart.write_ram(0xA000, 0x22);
+        cart.write_rom(0x6000, 0x00);
+        assert_eq!(cart.read_ram(0xA000), 0x11);
+        let save = cart.save_data().unwrap();
+        assert_eq!((save[0], save[2 * RAM_BANK_SIZE]), (0x11, 0x22));
+    }
+
+    #[test]
+    fn ram_reads_ff_until_enabled() {
+        let mut cart = Mbc1::new(numbered_banks(4), 0x2000);
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.read_ram(0xA000), 0xFF);
+        cart.write_rom(0x0000, 0x0A);
+        assert_eq!(cart.read_ram(0xA000), 0x00);
+        cart.write_ram(0xA000, 0x12);
+        cart.write_rom(0x0000, 0x00);
+        assert_eq!(cart.read_ram(0xA000), 0xFF);
+        cart.write_rom(0x0000, 0x1A);
+        assert_eq!(cart.read_ram(0xA000), 0x12);
+    }
+
+    #[test]
+    fn multicarts_put_bank_2_on_bits_4_and_5() {
+        let mut rom = numbered_banks(64);
+        let logo = 0x10 * ROM_BANK_SIZE + 0x104;
+        rom[logo..logo + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        let mut cart = Mbc1::new(rom, 0);
+        assert!(cart.is_multicart());
+        cart.write_rom(0x4000, 0x01);
+        cart.write_rom(0x2000, 0x13); // bit 4 of bank 1 isn't connected
+        assert_eq!(cart.read_rom(0x4000), 0x13);
+        cart.write_rom(0x6000, 0x01);
+        assert_eq!(cart.read_rom(0x0000), 0x10);
+        assert!(!Mbc1::new(numbered_banks(64), 0).is_multicart());
+    }
+}
@@ -0,0 +1,116 @@
+/* MBC2
+   0x0000 - 0x3FFF  address bit 8 picks the register:
+                      clear: ram enable, 0x0A in the low nibble enables
+                      set:   rom bank, 4 bits (0 reads as 1)
+   0xA000 - 0xBFFF  512 x 4 bit internal ram, mirrored every 0x200 bytes.
+                    Only the low nibble exists, the upper one reads back as 1s.
+*/
+use crate::cartridge::mapper::{banked_read, Cartridge, ROM_BANK_SIZE};
+
+const RAM_SIZE: usize = 0x200;
+
+pub struct Mbc2 {
+    rom: Vec<u8>,
+    ram: [u8; RAM_SIZE],
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Mbc2 {
+            rom,
+            ram: [0; RAM_SIZE],
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Cartridge for Mbc2 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        if address > 0x3FFF {
+            return;
+        }
+        if address & 0x0100 == 0 {
+            self.ram_enabled = value & 0x0F == 0x0A;
+        } else {
+            self.rom_bank = value & 0x0F;
+            if self.rom_bank == 0 {
+                self.rom_bank = 1;
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        0xF0 | self.ram[address as usize & (RAM_SIZE - 1)]
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ram_enabled {
+            self.ram[address as usize & (RAM_SIZE - 1)] = value & 0x0F;
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    // one byte per nibble, upper half zero, the same layout other emulators use
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.ram.to_vec())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        for (cell, byte) in self.ram.iter_mut().zip(data) {
+            *cell = byte & 0x0F;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::mapper::numbered_banks;
+
+    #[test]
+    fn address_bit_8_picks_the_register() {
+        let mut cart = Mbc2::new(numbered_banks(16));
+        cart.write_rom(0x2100, 0x05);
+        assert_eq!(cart.read_rom(0x4000), 5);
+        // bit 8 clear is the ram enable, the bank stays
+        cart.write_rom(0x2000, 0x0A);
+        assert_eq!(cart.read_rom(0x4000), 5);
+        assert_eq!(cart.read_ram(0xA000), 0xF0);
+        cart.write_rom(0x0100, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 1);
+    }
+
+    #[test]
+    fn ram_is_512_nibbles_mirrored() {
+        let mut cart = Mbc2::new(numbered_banks(2));
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_ram(0xA001, 0xAB);
+        assert_eq!(cart.read_ram(0xA001), 0xFB);
+        assert_eq!(cart.read_ram(0xA201), 0xFB);
+        assert_eq!(cart.read_ram(0xBE01), 0xFB);
+        let save = cart.save_data().unwrap();
+        assert_eq!((save.len(), save[1]), (0x200, 0x0B));
+
+        let mut loaded = Mbc2::new(numbered_banks(2));
+        loaded.load_save_data(&[0xFF; 0x200]);
+        loaded.write_rom(0x0000, 0x0A);
+        assert_eq!(loaded.read_ram(0xA000), 0xFF);
+        assert_eq!(loaded.save_data().unwrap()[0], 0x0F);
+    }
+}
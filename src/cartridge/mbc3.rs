@@ -0,0 +1,180 @@
+/* MBC3 / MBC30
+   0x0000 - 0x1FFF  ram and rtc enable, 0x0A in the low nibble enables
+   0x2000 - 0x3FFF  rom bank, 7 bits (8 on MBC30), 0 reads as 1
+   0x4000 - 0x5FFF  0x00 - 0x03 ram bank (0x07 on MBC30), 0x08 - 0x0C rtc register
+   0x6000 - 0x7FFF  rtc latch, write 0x00 then 0x01
+
+   MBC30 is the version used by the japanese Pocket Monsters Crystal, with 4 MiB of rom
+   and 64 KiB of ram. There is no header code for it so the sizes give it away.
+*/
+use crate::cartridge::mapper::{banked_read, banked_write, load_ram, Cartridge, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::cartridge::rtc::{ClockState, Rtc};
+
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Option<Rtc>, // only carts with a TIMER in the type have the clock chip
+    ram_enabled: bool,
+    rom_bank: u8,
+    select: u8, // ram bank or rtc register
+    mbc30: bool,
+}
+
+impl Mbc3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, has_rtc: bool) -> Self {
+        let mbc30 = rom.len() > 0x200000 || ram_size > 0x8000;
+        Mbc3 {
+            rom,
+            ram: vec![0; ram_size],
+            rtc: if has_rtc { Some(Rtc::new()) } else { None },
+            ram_enabled: false,
+            rom_bank: 1,
+            select: 0,
+            mbc30,
+        }
+    }
+
+    fn rtc_selected(&self) -> Option<u8> {
+        match self.select {
+            0x08..=0x0C if self.rtc.is_some() => Some(self.select),
+            _ => None,
+        }
+    }
+
+    fn ram_bank(&self) -> usize {
+        let mask = if self.mbc30 { 0x07 } else { 0x03 };
+        (self.select & mask) as usize
+    }
+}
+
+impl Cartridge for Mbc3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = if self.mbc30 { value } else { value & 0x7F };
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.select = value & 0x0F,
+            _ => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(value)
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        match (self.rtc_selected(), &self.rtc) {
+            (Some(register), Some(rtc)) => rtc.read(register),
+            _ if self.select <= 0x07 => banked_read(&self.ram, RAM_BANK_SIZE, self.ram_bank(), (address - 0xA000) as usize),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(register) = self.rtc_selected() {
+            if let Some(rtc) = &mut self.rtc {
+                rtc.write(register, value);
+            }
+        } else if self.select <= 0x07 {
+            let bank = self.ram_bank();
+            banked_write(&mut self.ram, RAM_BANK_SIZE, bank, (address - 0xA000) as usize, value);
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.tick(cycles);
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.ram.is_empty() { None } else { Some(self.ram.clone()) }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data)
+    }
+
+    fn rtc_state(&self) -> Option<ClockState> {
+        self.rtc.as_ref().map(|rtc| ClockState::Mbc3(rtc.state()))
+    }
+
+    fn load_rtc_state(&mut self, state: &ClockState, elapsed_seconds: u64) {
+        if let (Some(rtc), ClockState::Mbc3(state)) = (&mut self.rtc, state) {
+            rtc.load_state(state, elapsed_seconds);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::mapper::numbered_banks;
+    use crate::cartridge::rtc::CYCLES_PER_SECOND;
+
+    #[test]
+    fn rom_bank_is_7_bits_and_8_on_mbc30() {
+        let mut cart = Mbc3::new(numbered_banks(128), 0x8000, false);
+        cart.write_rom(0x2000, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 1);
+        cart.write_rom(0x2000, 0xFF);
+        assert_eq!(cart.read_rom(0x4000), 0x7F);
+        let mut mbc30 = Mbc3::new(numbered_banks(256), 0x10000, false);
+        mbc30.write_rom(0x2000, 0xFF);
+        assert_eq!(mbc30.read_rom(0x4000), 0xFF);
+    }
+
+    #[test]
+    fn ram_banks() {
+        let mut cart = Mbc3::new(numbered_banks(4), 0x8000, false);
+        cart.write_rom(0x0000, 0x0A);
+        for bank in 0..4 {
+            cart.write_rom(0x4000, bank);
+            cart.write_ram(0xA000, bank + 0x10);
+        }
+        cart.write_rom(0x4000, 0x02);
+        assert_eq!(cart.read_ram(0xA000), 0x12);
+        // without a clock the rtc registers are open bus
+        cart.write_rom(0x4000, 0x08);
+        assert_eq!(cart.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn the_clock_is_read_through_the_latch() {
+        let mut cart = Mbc3::new(numbered_banks(4), 0x2000, true);
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_rom(0x4000, 0x09);
+        cart.write_ram(0xA000, 0x2A);
+        assert_eq!(cart.read_ram(0xA000), 0xC0);
+        cart.write_rom(0x6000, 0x00);
+        cart.write_rom(0x6000, 0x01);
+        assert_eq!(cart.read_ram(0xA000), 0xEA);
+        cart.write_rom(0x4000, 0x08);
+        cart.tick(CYCLES_PER_SECOND * 3);
+        assert_eq!(cart.read_ram(0xA000), 0xC0);
+        cart.write_rom(0x6000, 0x00);
+        cart.write_rom(0x6000, 0x01);
+        assert_eq!(cart.read_ram(0xA000), 0xC3);
+    }
+}
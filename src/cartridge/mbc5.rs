@@ -0,0 +1,142 @@
+/* MBC5
+   0x0000 - 0x1FFF  ram enable, 0x0A in the low nibble enables
+   0x2000 - 0x2FFF  rom bank, low 8 bits
+   0x3000 - 0x3FFF  rom bank, bit 8. Bank 0 can be mapped at 0x4000 too
+   0x4000 - 0x5FFF  ram bank 0x00 - 0x0F. On rumble carts bit 3 drives the motor instead
+*/
+use crate::cartridge::mapper::{banked_read, banked_write, load_ram, Cartridge, CartridgeEvent, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+const RUMBLE_BIT: u8 = 0x08;
+
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    rumble: bool, // cart has a motor
+    motor_on: bool,
+    events: Vec<CartridgeEvent>,
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, rumble: bool) -> Self {
+        Mbc5 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumble,
+            motor_on: false,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn motor_on(&self) -> bool {
+        self.motor_on
+    }
+}
+
+impl Cartridge for Mbc5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 0x01) << 8),
+            0x4000..=0x5FFF => {
+                if self.rumble {
+                    let motor_on = value & RUMBLE_BIT != 0;
+                    if motor_on != self.motor_on {
+                        self.motor_on = motor_on;
+                        self.events.push(CartridgeEvent::Rumble(motor_on));
+                    }
+                    self.ram_bank = value & 0x07;
+                } else {
+                    self.ram_bank = value & 0x0F;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        banked_read(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (address - 0xA000) as usize)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ram_enabled {
+            banked_write(&mut self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (address - 0xA000) as usize, value);
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.ram.is_empty() { None } else { Some(self.ram.clone()) }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data)
+    }
+
+    fn take_events(&mut self) -> Vec<CartridgeEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::mapper::numbered_banks;
+
+    #[test]
+    fn nine_bit_rom_bank_and_bank_0_at_0x4000() {
+        let mut rom = numbered_banks(512);
+        rom[0x1FF * ROM_BANK_SIZE] = 0xAA;
+        let mut cart = Mbc5::new(rom, 0, false);
+        cart.write_rom(0x2000, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 0x00);
+        cart.write_rom(0x2000, 0xFF);
+        cart.write_rom(0x3000, 0x01);
+        assert_eq!(cart.read_rom(0x4000), 0xAA);
+        // the low byte write keeps bit 8
+        cart.write_rom(0x2000, 0xFF);
+        assert_eq!(cart.read_rom(0x4000), 0xAA);
+    }
+
+    #[test]
+    fn rumble_bit_drives_the_motor_not_the_ram_bank() {
+        let mut cart = Mbc5::new(numbered_banks(4), 0x20000, true);
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_rom(0x4000, 0x01);
+        cart.write_ram(0xA000, 0x11);
+        cart.write_rom(0x4000, 0x09);
+        assert!(cart.motor_on());
+        assert_eq!(cart.read_ram(0xA000), 0x11);
+        cart.write_rom(0x4000, 0x08);
+        cart.write_rom(0x4000, 0x00);
+        assert_eq!(cart.take_events(), vec![CartridgeEvent::Rumble(true), CartridgeEvent::Rumble(false)]);
+    }
+
+    #[test]
+    fn sixteen_ram_banks_without_rumble() {
+        let mut cart = Mbc5::new(numbered_banks(4), 0x20000, false);
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_rom(0x4000, 0x0F);
+        cart.write_ram(0xBFFF, 0x33);
+        assert_eq!(cart.save_data().unwrap()[0x1FFFF], 0x33);
+    }
+}
@@ -0,0 +1,350 @@
+/* MBC6
+   0x0000 - 0x03FF  ram enable, 0x0A enables
+   0x0400 - 0x07FF  ram bank A, 3 bits
+   0x0800 - 0x0BFF  ram bank B, 3 bits
+   0x0C00 - 0x0FFF  flash enable, bit 0
+   0x1000           flash write enable, bit 0
+   0x2000 - 0x27FF  rom/flash bank A, 7 bits
+   0x2800 - 0x2FFF  bank A source, 0x08 selects flash, 0x00 rom
+   0x3000 - 0x37FF  rom/flash bank B, 7 bits
+   0x3800 - 0x3FFF  bank B source
+   0x4000 - 0x5FFF  8 KiB window A
+   0x6000 - 0x7FFF  8 KiB window B
+   0xA000 - 0xAFFF  4 KiB ram window A
+   0xB000 - 0xBFFF  4 KiB ram window B
+   0x0000 - 0x3FFF always shows the first 16 KiB of rom.
+*/
+use crate::cartridge::mapper::{banked_read, banked_write, Cartridge, ROM_BANK_SIZE};
+
+const WINDOW_SIZE: usize = 0x2000;
+const RAM_WINDOW_SIZE: usize = 0x1000;
+const RAM_SIZE: usize = 0x8000;
+const FLASH_SIZE: usize = 0x100000;
+const FLASH_SECTOR_SIZE: usize = 0x20000;
+const FLASH_SECTORS: usize = FLASH_SIZE / FLASH_SECTOR_SIZE;
+
+// what the flash answers in autoselect mode
+const FLASH_MANUFACTURER_ID: u8 = 0xC2; // macronix
+const FLASH_DEVICE_ID: u8 = 0x81;
+
+pub struct Mbc6 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    flash: Flash,
+    ram_enabled: bool,
+    ram_bank_a: u8,
+    ram_bank_b: u8,
+    flash_enabled: bool,
+    bank_a: u8,
+    bank_b: u8,
+    flash_a: bool, // window A shows flash instead of rom
+    flash_b: bool,
+}
+
+impl Mbc6 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Mbc6 {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            flash: Flash::new(),
+            ram_enabled: false,
+            ram_bank_a: 0,
+            ram_bank_b: 0,
+            flash_enabled: false,
+            bank_a: 0,
+            bank_b: 0,
+            flash_a: false,
+            flash_b: false,
+        }
+    }
+
+    fn window(&self, address: u16) -> (u8, bool) {
+        if address < 0x6000 {
+            (self.bank_a, self.flash_a)
+        } else {
+            (self.bank_b, self.flash_b)
+        }
+    }
+
+    fn ram_bank(&self, address: u16) -> usize {
+        if address < 0xB000 { self.ram_bank_a as usize } else { self.ram_bank_b as usize }
+    }
+}
+
+impl Cartridge for Mbc6 {
+    fn read_rom(&self, address: u16) -> u8 {
+        if address < 0x4000 {
+            return banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize);
+        }
+        let (bank, flash) = self.window(address);
+        let offset = address as usize & (WINDOW_SIZE - 1);
+        if flash {
+            if !self.flash_enabled {
+                return 0xFF;
+            }
+            self.flash.read(bank as usize * WINDOW_SIZE + offset)
+        } else {
+            banked_read(&self.rom, WINDOW_SIZE, bank as usize, offset)
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x03FF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x0400..=0x07FF => self.ram_bank_a = value & 0x07,
+            0x0800..=0x0BFF => self.ram_bank_b = value & 0x07,
+            0x0C00..=0x0FFF => self.flash_enabled = value & 0x01 != 0,
+            0x1000 => self.flash.write_enabled = value & 0x01 != 0,
+            0x2000..=0x27FF => self.bank_a = value & 0x7F,
+            0x2800..=0x2FFF => self.flash_a = value == 0x08,
+            0x3000..=0x37FF => self.bank_b = value & 0x7F,
+            0x3800..=0x3FFF => self.flash_b = value == 0x08,
+            0x4000..=0x7FFF => {
+                let (bank, flash) = self.window(address);
+                if flash && self.flash_enabled {
+                    let offset = address as usize & (WINDOW_SIZE - 1);
+                    self.flash.write(bank as usize * WINDOW_SIZE + offset, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        banked_read(&self.ram, RAM_WINDOW_SIZE, self.ram_bank(address), address as usize & (RAM_WINDOW_SIZE - 1))
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ram_enabled {
+            let bank = self.ram_bank(address);
+            banked_write(&mut self.ram, RAM_WINDOW_SIZE, bank, address as usize & (RAM_WINDOW_SIZE - 1), value);
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    // ram followed by the whole flash chip
+    fn save_data(&self) -> Option<Vec<u8>> {
+        let mut data = self.ram.clone();
+        data.extend_from_slice(&self.flash.data);
+        Some(data)
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let (ram, flash) = data.split_at(data.len().min(RAM_SIZE));
+        self.ram[..ram.len()].copy_from_slice(ram);
+        let len = flash.len().min(FLASH_SIZE);
+        self.flash.data[..len].copy_from_slice(&flash[..len]);
+    }
+}
+
+/* MX29F008 style flash. Commands are unlocked by writing 0xAA to 0x5555 then 0x55 to 0x2AAA
+   (flash addresses, upper bits ignored), followed by the command byte:
+     0xA0 at 0x5555            program the next byte written (bits can only go 1 -> 0)
+     0x80 at 0x5555, unlock,
+       then 0x10 at 0x5555     erase the whole chip
+       or   0x30 at a sector   erase that 128 KiB sector
+     0x60 at 0x5555, unlock,
+       then 0x40 at a sector   write protect that sector
+       or   0x20 at 0x5555     remove every sector's protection
+     0x90 at 0x5555            autoselect, reads return the chip ids
+     0xF0 anywhere             back to reading the array (unless it is the byte being programmed)
+   Nothing is written unless the MBC's flash write enable is set. Programming and
+   erasing finish instantly.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FlashState {
+    Read,
+    Unlock1, // got 0xAA
+    Unlock2, // got 0x55, waiting for the command
+    Program,
+    EraseSetup,
+    EraseUnlock1,
+    EraseUnlock2,
+    ProtectSetup,
+    ProtectUnlock1,
+    ProtectUnlock2,
+    Autoselect,
+}
+
+struct Flash {
+    data: Vec<u8>,
+    state: FlashState,
+    protected: [bool; FLASH_SECTORS],
+    write_enabled: bool,
+}
+
+impl Flash {
+    fn new() -> Self {
+        Flash {
+            data: vec![0xFF; FLASH_SIZE],
+            state: FlashState::Read,
+            protected: [false; FLASH_SECTORS],
+            write_enabled: false,
+        }
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        let address = address % FLASH_SIZE;
+        match self.state {
+            FlashState::Autoselect => match address & 0xFF {
+                0x00 => FLASH_MANUFACTURER_ID,
+                0x01 => FLASH_DEVICE_ID,
+                0x02 => self.protected[address / FLASH_SECTOR_SIZE] as u8,
+                _ => 0x00,
+            },
+            _ => self.data[address],
+        }
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        let address = address % FLASH_SIZE;
+        let command_address = address & 0x7FFF;
+        // a byte being programmed is data, even when it looks like the reset
+        if value == 0xF0 && self.state != FlashState::Program {
+            self.state = FlashState::Read;
+            return;
+        }
+        self.state = match (self.state, command_address, value) {
+            (FlashState::Read | FlashState::Autoselect, 0x5555, 0xAA) => FlashState::Unlock1,
+            (FlashState::Unlock1, 0x2AAA, 0x55) => FlashState::Unlock2,
+            (FlashState::Unlock2, 0x5555, 0xA0) => FlashState::Program,
+            (FlashState::Unlock2, 0x5555, 0x80) => FlashState::EraseSetup,
+            (FlashState::Unlock2, 0x5555, 0x60) => FlashState::ProtectSetup,
+            (FlashState::Unlock2, 0x5555, 0x90) => FlashState::Autoselect,
+            (FlashState::Program, _, _) => {
+                if self.writable(address) {
+                    self.data[address] &= value;
+                }
+                FlashState::Read
+            }
+            (FlashState::EraseSetup, 0x5555, 0xAA) => FlashState::EraseUnlock1,
+            (FlashState::EraseUnlock1, 0x2AAA, 0x55) => FlashState::EraseUnlock2,
+            (FlashState::EraseUnlock2, 0x5555, 0x10) => {
+                for sector in 0..FLASH_SECTORS {
+                    self.erase_sector(sector);
+                }
+                FlashState::Read
+            }
+            (FlashState::EraseUnlock2, _, 0x30) => {
+                self.erase_sector(address / FLASH_SECTOR_SIZE);
+                FlashState::Read
+            }
+            (FlashState::ProtectSetup, 0x5555, 0xAA) => FlashState::ProtectUnlock1,
+            (FlashState::ProtectUnlock1, 0x2AAA, 0x55) => FlashState::ProtectUnlock2,
+            (FlashState::ProtectUnlock2, _, 0x40) => {
+                if self.write_enabled {
+                    self.protected[address / FLASH_SECTOR_SIZE] = true;
+                }
+                FlashState::Read
+            }
+            (FlashState::ProtectUnlock2, 0x5555, 0x20) => {
+                if self.write_enabled {
+                    self.protected = [false; FLASH_SECTORS];
+                }
+                FlashState::Read
+            }
+            // anything out of sequence drops back to reading
+            _ => FlashState::Read,
+        };
+    }
+
+    fn writable(&self, address: usize) -> bool {
+        self.write_enabled && !self.protected[address / FLASH_SECTOR_SIZE]
+    }
+
+    fn erase_sector(&mut self, sector: usize) {
+        let start = sector * FLASH_SECTOR_SIZE;
+        if self.writable(start) {
+            self.data[start..start + FLASH_SECTOR_SIZE].fill(0xFF);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cart() -> Mbc6 {
+        Mbc6::new((0..128 * WINDOW_SIZE).map(|i| (i / WINDOW_SIZE) as u8).collect())
+    }
+
+    // window A on flash bank 2 and window B on flash bank 1 put 0x5555 and 0x2AAA at 0x5555 and 0x6AAA
+    fn flash_cart() -> Mbc6 {
+        let mut cart = cart();
+        cart.write_rom(0x0C00, 0x01);
+        cart.write_rom(0x1000, 0x01);
+        cart.write_rom(0x2000, 0x02);
+        cart.write_rom(0x2800, 0x08);
+        cart.write_rom(0x3000, 0x01);
+        cart.write_rom(0x3800, 0x08);
+        cart
+    }
+
+    fn flash_command(cart: &mut Mbc6, command: u8) {
+        cart.write_rom(0x5555, 0xAA);
+        cart.write_rom(0x6AAA, 0x55);
+        cart.write_rom(0x5555, command);
+    }
+
+    #[test]
+    fn two_8k_rom_windows() {
+        let mut cart = cart();
+        cart.write_rom(0x2000, 0x03);
+        cart.write_rom(0x3000, 0x45);
+        assert_eq!((cart.read_rom(0x3FFF), cart.read_rom(0x4000), cart.read_rom(0x7FFF)), (1, 3, 0x45));
+    }
+
+    #[test]
+    fn two_4k_ram_windows() {
+        let mut cart = cart();
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_rom(0x0400, 0x01);
+        cart.write_rom(0x0800, 0x06);
+        cart.write_ram(0xA000, 0x11);
+        cart.write_ram(0xB000, 0x66);
+        let save = cart.save_data().unwrap();
+        assert_eq!((save[RAM_WINDOW_SIZE], save[6 * RAM_WINDOW_SIZE]), (0x11, 0x66));
+    }
+
+    #[test]
+    fn flash_program_and_erase() {
+        let mut cart = flash_cart();
+        flash_command(&mut cart, 0xA0);
+        cart.write_rom(0x4000, 0x3C);
+        assert_eq!(cart.read_rom(0x4000), 0x3C);
+        // programming only clears bits
+        flash_command(&mut cart, 0xA0);
+        cart.write_rom(0x4000, 0xF0);
+        assert_eq!(cart.read_rom(0x4000), 0x30);
+        flash_command(&mut cart, 0x80);
+        cart.write_rom(0x5555, 0xAA);
+        cart.write_rom(0x6AAA, 0x55);
+        cart.write_rom(0x4000, 0x30);
+        assert_eq!(cart.read_rom(0x4000), 0xFF);
+        assert_eq!(cart.save_data().unwrap()[RAM_SIZE + 2 * WINDOW_SIZE], 0xFF);
+    }
+
+    #[test]
+    fn flash_needs_the_write_enable() {
+        let mut cart = flash_cart();
+        cart.write_rom(0x1000, 0x00);
+        flash_command(&mut cart, 0xA0);
+        cart.write_rom(0x4000, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 0xFF);
+    }
+
+    #[test]
+    fn flash_autoselect_reads_the_ids() {
+        let mut cart = flash_cart();
+        flash_command(&mut cart, 0x90);
+        assert_eq!((cart.read_rom(0x4000), cart.read_rom(0x4001)), (FLASH_MANUFACTURER_ID, FLASH_DEVICE_ID));
+        cart.write_rom(0x4000, 0xF0);
+        assert_eq!(cart.read_rom(0x4000), 0xFF);
+    }
+}
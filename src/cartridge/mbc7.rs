@@ -0,0 +1,371 @@
+/* MBC7
+   0x0000 - 0x1FFF  ram enable 1, 0x0A enables
+   0x2000 - 0x3FFF  rom bank, 7 bits
+   0x4000 - 0x5FFF  ram enable 2, 0x40 enables
+   0xA000 - 0xAFFF  register block when both enables are set, address bits 4-7 pick the register:
+     Ax0x  write 0x55 to erase the latched accelerometer values
+     Ax1x  write 0xAA (after an erase) to latch the accelerometer
+     Ax2x  x low    Ax3x  x high
+     Ax4x  y low    Ax5x  y high
+     Ax6x  reads 0x00
+     Ax7x  reads 0xFF
+     Ax8x  eeprom pins: bit 7 CS, bit 6 CLK, bit 1 DI, bit 0 DO (read only)
+   Everything else reads 0xFF.
+*/
+use crate::cartridge::mapper::{banked_read, Cartridge, HostInput, ROM_BANK_SIZE};
+
+// accelerometer reading when the cart is held flat, and how far one g moves it
+const ACCEL_CENTER: f32 = 0x81D0 as f32;
+const ACCEL_PER_G: f32 = 0x70 as f32;
+const ACCEL_ERASED: u16 = 0x8000;
+
+const EEPROM_WORDS: usize = 128; // 93LC56 in 16 bit mode, 2 Kbit
+
+const CS: u8 = 0x80;
+const CLK: u8 = 0x40;
+const DI: u8 = 0x02;
+const DO: u8 = 0x01;
+
+pub struct Mbc7 {
+    rom: Vec<u8>,
+    ram_enabled_1: bool,
+    ram_enabled_2: bool,
+    rom_bank: u8,
+    tilt_x: f32,
+    tilt_y: f32,
+    latched_x: u16,
+    latched_y: u16,
+    latch_erased: bool,
+    eeprom: Eeprom,
+}
+
+impl Mbc7 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Mbc7 {
+            rom,
+            ram_enabled_1: false,
+            ram_enabled_2: false,
+            rom_bank: 1,
+            tilt_x: 0.0,
+            tilt_y: 0.0,
+            latched_x: ACCEL_ERASED,
+            latched_y: ACCEL_ERASED,
+            latch_erased: false,
+            eeprom: Eeprom::new(),
+        }
+    }
+
+    // tilt in g on each axis, positive x is right and positive y is down
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt_x = x;
+        self.tilt_y = y;
+    }
+
+    fn registers_enabled(&self) -> bool {
+        self.ram_enabled_1 && self.ram_enabled_2
+    }
+}
+
+fn accelerometer_value(g: f32) -> u16 {
+    (ACCEL_CENTER + g * ACCEL_PER_G).clamp(0.0, u16::MAX as f32) as u16
+}
+
+impl Cartridge for Mbc7 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled_1 = value == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = value & 0x7F,
+            0x4000..=0x5FFF => self.ram_enabled_2 = value == 0x40,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.registers_enabled() || address > 0xAFFF {
+            return 0xFF;
+        }
+        match (address >> 4) & 0x0F {
+            0x2 => self.latched_x as u8,
+            0x3 => (self.latched_x >> 8) as u8,
+            0x4 => self.latched_y as u8,
+            0x5 => (self.latched_y >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.pins(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.registers_enabled() || address > 0xAFFF {
+            return;
+        }
+        match (address >> 4) & 0x0F {
+            0x0 if value == 0x55 => {
+                self.latched_x = ACCEL_ERASED;
+                self.latched_y = ACCEL_ERASED;
+                self.latch_erased = true;
+            }
+            0x1 if value == 0xAA && self.latch_erased => {
+                self.latched_x = accelerometer_value(self.tilt_x);
+                self.latched_y = accelerometer_value(self.tilt_y);
+                self.latch_erased = false;
+            }
+            0x8 => self.eeprom.write_pins(value),
+            _ => {}
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.registers_enabled()
+    }
+
+    // the eeprom is the save, stored as little endian words
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.eeprom.data.iter().flat_map(|word| word.to_le_bytes()).collect())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        for (word, bytes) in self.eeprom.data.iter_mut().zip(data.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+
+    fn host_input(&mut self, input: HostInput) {
+        if let HostInput::Tilt { x, y } = input {
+            self.set_tilt(x, y)
+        }
+    }
+}
+
+/* 93LC56 serial eeprom, bit banged through the Ax8x register.
+   With CS high every rising CLK edge shifts DI in. A command is a 1 start bit,
+   2 opcode bits and 8 address bits (the top one unused in 16 bit mode):
+     10 aaaaaaaa                 READ   word out on DO after a dummy 0
+     01 aaaaaaaa dddd(16)        WRITE
+     11 aaaaaaaa                 ERASE  word becomes 0xFFFF
+     00 11xxxxxx                 EWEN   allow writes
+     00 00xxxxxx                 EWDS   forbid writes
+     00 10xxxxxx                 ERAL   erase everything
+     00 01xxxxxx dddd(16)        WRAL   write everything
+   Writes take no time here, DO reads 1 (ready) as soon as they're done.
+   Dropping CS aborts whatever was going on.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EepromState {
+    Idle, // waiting for the start bit
+    Command,
+    WriteData { address: Option<usize> }, // None writes every word (WRAL)
+    Read { value: u16 },
+    Done,
+}
+
+struct Eeprom {
+    data: [u16; EEPROM_WORDS],
+    cs: bool,
+    clk: bool,
+    di: bool,
+    do_bit: bool,
+    write_enabled: bool,
+    state: EepromState,
+    shift: u16,
+    bits: u8,
+}
+
+impl Eeprom {
+    fn new() -> Self {
+        Eeprom {
+            data: [0xFFFF; EEPROM_WORDS],
+            cs: false,
+            clk: false,
+            di: false,
+            do_bit: true,
+            write_enabled: false,
+            state: EepromState::Idle,
+            shift: 0,
+            bits: 0,
+        }
+    }
+
+    fn pins(&self) -> u8 {
+        (if self.cs { CS } else { 0 })
+            | (if self.clk { CLK } else { 0 })
+            | (if self.di { DI } else { 0 })
+            | (if self.do_bit { DO } else { 0 })
+    }
+
+    fn write_pins(&mut self, value: u8) {
+        let cs = value & CS != 0;
+        let clk = value & CLK != 0;
+        self.di = value & DI != 0;
+        if !cs {
+            self.state = EepromState::Idle;
+            self.do_bit = true;
+        } else if clk && !self.clk {
+            self.rising_edge();
+        }
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn rising_edge(&mut self) {
+        let bit = self.di as u16;
+        match self.state {
+            EepromState::Idle => {
+                if self.di {
+                    self.state = EepromState::Command;
+                    self.shift = 0;
+                    self.bits = 0;
+                }
+            }
+            EepromState::Command => {
+                self.shift = (self.shift << 1) | bit;
+                self.bits += 1;
+                if self.bits == 10 {
+                    self.run_command();
+                }
+            }
+            EepromState::WriteData { address } => {
+                self.shift = (self.shift << 1) | bit;
+                self.bits += 1;
+                if self.bits == 16 {
+                    if self.write_enabled {
+                        match address {
+                            Some(address) => self.data[address] = self.shift,
+                            None => self.data = [self.shift; EEPROM_WORDS],
+                        }
+                    }
+                    self.do_bit = true;
+                    self.state = EepromState::Done;
+                }
+            }
+            EepromState::Read { value } => {
+                self.do_bit = value & 0x8000 != 0;
+                self.state = EepromState::Read { value: value << 1 };
+            }
+            EepromState::Done => {}
+        }
+    }
+
+    fn run_command(&mut self) {
+        let opcode = (self.shift >> 8) & 0x03;
+        let field = (self.shift & 0xFF) as usize;
+        let address = field & 0x7F;
+        self.shift = 0;
+        self.bits = 0;
+        self.state = EepromState::Done;
+        match opcode {
+            0b10 => {
+                self.do_bit = false; // dummy bit before the data
+                self.state = EepromState::Read { value: self.data[address] };
+            }
+            0b01 => self.state = EepromState::WriteData { address: Some(address) },
+            0b11 => {
+                if self.write_enabled {
+                    self.data[address] = 0xFFFF;
+                }
+            }
+            _ => match field >> 6 {
+                0b11 => self.write_enabled = true,
+                0b00 => self.write_enabled = false,
+                0b10 => {
+                    if self.write_enabled {
+                        self.data = [0xFFFF; EEPROM_WORDS];
+                    }
+                }
+                _ => self.state = EepromState::WriteData { address: None },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled_cart() -> Mbc7 {
+        let mut cart = Mbc7::new(vec![0; 0x8000]);
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_rom(0x4000, 0x40);
+        cart
+    }
+
+    // clocks the low `count` bits of `bits` into the eeprom, high bit first, and returns what DO showed after each edge
+    fn send(cart: &mut Mbc7, bits: u32, count: u32) -> u32 {
+        let mut out = 0;
+        for i in (0..count).rev() {
+            let di = if bits >> i & 1 != 0 { DI } else { 0 };
+            cart.write_ram(0xA080, CS | di);
+            cart.write_ram(0xA080, CS | CLK | di);
+            out = (out << 1) | (cart.read_ram(0xA080) & DO) as u32;
+        }
+        out
+    }
+
+    // start bit, 2 opcode bits and the 8 bit address field
+    fn command(cart: &mut Mbc7, opcode: u32, field: u32) {
+        send(cart, 0x400 | opcode << 8 | field, 11);
+    }
+
+    fn deselect(cart: &mut Mbc7) {
+        cart.write_ram(0xA080, 0);
+    }
+
+    fn latched(cart: &Mbc7) -> (u16, u16) {
+        let x = u16::from_le_bytes([cart.read_ram(0xA020), cart.read_ram(0xA030)]);
+        let y = u16::from_le_bytes([cart.read_ram(0xA040), cart.read_ram(0xA050)]);
+        (x, y)
+    }
+
+    #[test]
+    fn accelerometer_latches_after_erase() {
+        let mut cart = enabled_cart();
+        cart.host_input(HostInput::Tilt { x: 1.0, y: -1.0 });
+        cart.write_ram(0xA000, 0x55);
+        assert_eq!(latched(&cart), (0x8000, 0x8000));
+        cart.write_ram(0xA010, 0xAA);
+        assert_eq!(latched(&cart), (0x81D0 + 0x70, 0x81D0 - 0x70));
+        // a second latch without an erase before it keeps the old values
+        cart.host_input(HostInput::Tilt { x: 0.0, y: 0.0 });
+        cart.write_ram(0xA010, 0xAA);
+        assert_eq!(latched(&cart), (0x81D0 + 0x70, 0x81D0 - 0x70));
+        cart.write_ram(0xA000, 0x55);
+        cart.write_ram(0xA010, 0xAA);
+        assert_eq!(latched(&cart), (0x81D0, 0x81D0));
+    }
+
+    #[test]
+    fn registers_need_both_enables() {
+        let mut cart = Mbc7::new(vec![0; 0x8000]);
+        cart.write_rom(0x0000, 0x0A);
+        assert_eq!(cart.read_ram(0xA060), 0xFF);
+        cart.write_rom(0x4000, 0x40);
+        assert_eq!(cart.read_ram(0xA060), 0x00);
+        assert_eq!(cart.read_ram(0xB060), 0xFF);
+    }
+
+    #[test]
+    fn eeprom_write_then_read() {
+        let mut cart = enabled_cart();
+        // writes are ignored until EWEN
+        command(&mut cart, 0b01, 5);
+        send(&mut cart, 0x1234, 16);
+        deselect(&mut cart);
+        assert_eq!(&cart.save_data().unwrap()[10..12], &[0xFF, 0xFF]);
+        command(&mut cart, 0b00, 0xC0); // EWEN
+        deselect(&mut cart);
+        command(&mut cart, 0b01, 5);
+        send(&mut cart, 0xBEEF, 16);
+        deselect(&mut cart);
+        command(&mut cart, 0b10, 5);
+        assert_eq!(send(&mut cart, 0, 16), 0xBEEF);
+        deselect(&mut cart);
+        assert_eq!(&cart.save_data().unwrap()[10..12], &[0xEF, 0xBE]);
+    }
+}
@@ -0,0 +1,217 @@
+/* MMM01
+   A multicart mapper. It powers up "unmapped", showing the menu in the last 32 KiB of the
+   rom, and the menu then sets a base bank and masks for the sub game and locks them in by
+   setting the map enable bit. From then on the game sees something close to MBC1.
+
+   0x0000 - 0x1FFF  bits 0-3 ram enable (0x0A), bits 4-5 ram bank mask*, bit 6 map enable*
+   0x2000 - 0x3FFF  bits 0-4 rom bank low, bits 5-6 rom bank mid*
+   0x4000 - 0x5FFF  bits 0-1 ram bank low, bits 2-3 ram bank high*, bits 4-5 rom bank high*,
+                    bit 6 disable mode writes*
+   0x6000 - 0x7FFF  bit 0 mode, bits 2-5 rom bank mask*, bit 6 multiplex*
+   * only writable while unmapped
+
+   A set bit in the rom bank mask freezes bits 1-4 of the rom bank low register at the value
+   the menu left, a set bit in the ram bank mask does the same for the ram bank low bits.
+   Bank numbers are 9 bits and wrap to the size of the image, so the 0x1FE/0x1FF banks
+   forced while unmapped land at the end of the rom where the menu lives.
+*/
+use crate::cartridge::mapper::{banked_read, banked_write, load_ram, Cartridge, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+pub struct Mmm01 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mapped: bool,
+    ram_enabled: bool,
+    ram_bank_mask: u8,
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    mode_locked: bool,
+    mode: bool,
+    rom_bank_mask: u8,
+    multiplex: bool,
+}
+
+impl Mmm01 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        Mmm01 {
+            rom,
+            ram: vec![0; ram_size],
+            mapped: false,
+            ram_enabled: false,
+            ram_bank_mask: 0,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            mode_locked: false,
+            mode: false,
+            rom_bank_mask: 0,
+            multiplex: false,
+        }
+    }
+
+    // bits of the rom bank low register the game can't change
+    fn locked_rom_bits(&self) -> u8 {
+        (self.rom_bank_mask << 1) & 0x1E
+    }
+
+    // with multiplex on, the mid rom bits and the low ram bits swap places
+    fn rom_mid_and_ram_low(&self) -> (u8, u8) {
+        if self.multiplex {
+            (self.ram_bank_low, self.rom_bank_mid)
+        } else {
+            (self.rom_bank_mid, self.ram_bank_low)
+        }
+    }
+
+    fn rom_base(&self) -> usize {
+        let (mid, _) = self.rom_mid_and_ram_low();
+        ((self.rom_bank_high as usize) << 7) | ((mid as usize) << 5)
+    }
+
+    fn low_bank(&self) -> usize {
+        if !self.mapped {
+            return 0x1FE;
+        }
+        self.rom_base() | (self.rom_bank_low & self.locked_rom_bits()) as usize
+    }
+
+    fn high_bank(&self) -> usize {
+        if !self.mapped {
+            return 0x1FF;
+        }
+        let low = if self.rom_bank_low & !self.locked_rom_bits() == 0 {
+            self.rom_bank_low | 0x01 // same bank 0 -> 1 quirk as MBC1
+        } else {
+            self.rom_bank_low
+        };
+        self.rom_base() | low as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        let (_, low) = self.rom_mid_and_ram_low();
+        let low = if self.mode { low } else { low & self.ram_bank_mask };
+        ((self.ram_bank_high as usize) << 2) | low as usize
+    }
+}
+
+impl Cartridge for Mmm01 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, self.low_bank(), address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.high_bank(), (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = value & 0x0F == 0x0A;
+                if !self.mapped {
+                    self.ram_bank_mask = (value >> 4) & 0x03;
+                    self.mapped = value & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                let writable = if self.mapped { 0x1F & !self.locked_rom_bits() } else { 0x1F };
+                self.rom_bank_low = (self.rom_bank_low & !writable) | (value & writable);
+                if !self.mapped {
+                    self.rom_bank_mid = (value >> 5) & 0x03;
+                }
+            }
+            0x4000..=0x5FFF => {
+                let writable = if self.mapped { 0x03 & !self.ram_bank_mask } else { 0x03 };
+                self.ram_bank_low = (self.ram_bank_low & !writable) | (value & writable);
+                if !self.mapped {
+                    self.ram_bank_high = (value >> 2) & 0x03;
+                    self.rom_bank_high = (value >> 4) & 0x03;
+                    self.mode_locked = value & 0x40 != 0;
+                }
+            }
+            _ => {
+                if !self.mapped || !self.mode_locked {
+                    self.mode = value & 0x01 != 0;
+                }
+                if !self.mapped {
+                    self.rom_bank_mask = (value >> 2) & 0x0F;
+                    self.multiplex = value & 0x40 != 0;
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        banked_read(&self.ram, RAM_BANK_SIZE, self.ram_bank(), (address - 0xA000) as usize)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ram_enabled {
+            let bank = self.ram_bank();
+            banked_write(&mut self.ram, RAM_BANK_SIZE, bank, (address - 0xA000) as usize, value);
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.ram.is_empty() { None } else { Some(self.ram.clone()) }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::mapper::{self, numbered_banks};
+
+    #[test]
+    fn boots_into_the_menu_at_the_end() {
+        let cart = Mmm01::new(numbered_banks(64), 0);
+        assert_eq!((cart.read_rom(0x0000), cart.read_rom(0x4000)), (62, 63));
+    }
+
+    #[test]
+    fn the_header_is_found_with_the_menu() {
+        let mut rom = numbered_banks(64);
+        rom[62 * ROM_BANK_SIZE + 0x147] = 0x0B;
+        assert_eq!(mapper::load(rom).read_rom(0x0000), 62);
+    }
+
+    #[test]
+    fn the_menu_maps_a_sub_game_and_locks_it_in() {
+        let mut cart = Mmm01::new(numbered_banks(64), 0);
+        cart.write_rom(0x2000, 0x20); // base bank 32
+        cart.write_rom(0x6000, 0x0C << 2); // bits 3-4 of the bank belong to the menu, an 8 bank game
+        cart.write_rom(0x0000, 0x40);
+        assert_eq!((cart.read_rom(0x0000), cart.read_rom(0x4000)), (32, 33));
+        cart.write_rom(0x2000, 0xFF);
+        assert_eq!(cart.read_rom(0x4000), 39);
+        // the menu's registers don't take writes any more
+        cart.write_rom(0x4000, 0x30);
+        cart.write_rom(0x0000, 0x00);
+        assert_eq!((cart.read_rom(0x0000), cart.read_rom(0x4000)), (32, 39));
+    }
+
+    #[test]
+    fn ram_bank_mask() {
+        let mut cart = Mmm01::new(numbered_banks(64), 0x8000);
+        cart.write_rom(0x4000, 0x02);
+        cart.write_rom(0x0000, 0x4A | 0x20); // ram enabled, bit 1 of the ram bank frozen
+        cart.write_rom(0x6000, 0x01);
+        cart.write_rom(0x4000, 0x01);
+        cart.write_ram(0xA000, 0x33);
+        assert_eq!(cart.save_data().unwrap()[3 * RAM_BANK_SIZE], 0x33);
+    }
+}
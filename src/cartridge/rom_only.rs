@@ -0,0 +1,42 @@
+use crate::cartridge::mapper::{load_ram, Cartridge};
+
+// 32 KiB of rom mapped straight in, optionally with a single bank of ram
+pub struct RomOnly {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        RomOnly {
+            rom,
+            ram: vec![0; ram_size.min(0x2000)],
+        }
+    }
+}
+
+impl Cartridge for RomOnly {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom.get(address as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _address: u16, _value: u8) {}
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram.get((address - 0xA000) as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(byte) = self.ram.get_mut((address - 0xA000) as usize) {
+            *byte = value
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.ram.is_empty() { None } else { Some(self.ram.clone()) }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data)
+    }
+}
@@ -0,0 +1,320 @@
+/* MBC3 real time clock
+   0x08  seconds  0-59
+   0x09  minutes  0-59
+   0x0A  hours    0-23
+   0x0B  day counter, low 8 bits
+   0x0C  bit 0 day counter bit 8, bit 6 halt, bit 7 day counter carry
+
+   The registers count on their own, the cpu only sees the copy made by the latch
+   sequence (write 0x00 then 0x01 to 0x6000 - 0x7FFF).
+*/
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const CYCLES_PER_SECOND: u32 = 4_194_304;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+const DAY_HIGH_BIT: u8 = 0x01;
+const HALT_BIT: u8 = 0x40;
+const CARRY_BIT: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RtcRegisters {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    pub days_low: u8,
+    pub days_high: u8,
+}
+
+// everything needed to carry the clock from one session to the next
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RtcState {
+    pub current: RtcRegisters,
+    pub latched: RtcRegisters,
+    pub timestamp: u64, // unix seconds when the state was taken
+}
+
+// HuC3's clock, minutes of the day and a day counter, and its alarm (see huc3.rs)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HuC3Clock {
+    pub minutes: u16,
+    pub days: u16,
+    pub alarm_minutes: u16,
+    pub alarm_days: u16,
+    pub alarm_enabled: bool,
+    pub timestamp: u64, // unix seconds when the state was taken
+}
+
+/* HuC3's clock in MBC3 registers, for the save trailer: the minute of the day as hours and
+   minutes, the 12 bit day counter in days low and the low nibble of days high. The alarm
+   goes in the latched registers the same way, with the seconds at 1 while it's armed. */
+impl HuC3Clock {
+    pub fn to_rtc_state(&self) -> RtcState {
+        let registers = |minutes: u16, days: u16, seconds: u8| RtcRegisters {
+            seconds,
+            minutes: (minutes % 60) as u8,
+            hours: (minutes / 60) as u8,
+            days_low: days as u8,
+            days_high: ((days >> 8) & 0x0F) as u8,
+        };
+        RtcState {
+            current: registers(self.minutes, self.days, 0),
+            latched: registers(self.alarm_minutes, self.alarm_days, self.alarm_enabled as u8),
+            timestamp: self.timestamp,
+        }
+    }
+
+    pub fn from_rtc_state(state: &RtcState) -> Self {
+        let minutes = |regs: &RtcRegisters| regs.hours as u16 * 60 + regs.minutes as u16;
+        let days = |regs: &RtcRegisters| ((regs.days_high as u16 & 0x0F) << 8) | regs.days_low as u16;
+        HuC3Clock {
+            minutes: minutes(&state.current),
+            days: days(&state.current),
+            alarm_minutes: minutes(&state.latched),
+            alarm_days: days(&state.latched),
+            alarm_enabled: state.latched.seconds == 1,
+            timestamp: state.timestamp,
+        }
+    }
+}
+
+// a cart's clock in the shape its save format wants, see save.rs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockState {
+    Mbc3(RtcState),
+    HuC3(HuC3Clock),
+}
+
+impl ClockState {
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            ClockState::Mbc3(state) => state.timestamp,
+            ClockState::HuC3(clock) => clock.timestamp,
+        }
+    }
+
+    pub fn with_timestamp(self, timestamp: u64) -> Self {
+        match self {
+            ClockState::Mbc3(state) => ClockState::Mbc3(RtcState { timestamp, ..state }),
+            ClockState::HuC3(clock) => ClockState::HuC3(HuC3Clock { timestamp, ..clock }),
+        }
+    }
+}
+
+pub struct Rtc {
+    current: RtcRegisters,
+    latched: RtcRegisters,
+    cycles: u32, // progress towards the next second
+    latch_armed: bool, // 0x00 was written, a 0x01 latches
+}
+
+impl Default for Rtc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rtc {
+    pub fn new() -> Self {
+        Rtc {
+            current: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            cycles: 0,
+            latch_armed: false,
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        if self.halted() {
+            return;
+        }
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_SECOND {
+            self.cycles -= CYCLES_PER_SECOND;
+            self.tick_second();
+        }
+    }
+
+    pub fn advance_seconds(&mut self, mut seconds: u64) {
+        if self.halted() {
+            return;
+        }
+        // out of range values tick up to their wraparound without carrying, step them by hand
+        while seconds > 0 && !self.in_range() {
+            self.tick_second();
+            seconds -= 1;
+        }
+        // with no time left an out of range value has to stay as it is
+        if seconds == 0 {
+            return;
+        }
+        let regs = &mut self.current;
+        let days = (((regs.days_high & DAY_HIGH_BIT) as u64) << 8) | regs.days_low as u64;
+        let total = days * SECONDS_PER_DAY
+            + regs.hours as u64 * 3600
+            + regs.minutes as u64 * 60
+            + regs.seconds as u64
+            + seconds;
+        let mut days = total / SECONDS_PER_DAY;
+        if days >= 512 {
+            regs.days_high |= CARRY_BIT;
+            days %= 512;
+        }
+        regs.seconds = (total % 60) as u8;
+        regs.minutes = (total / 60 % 60) as u8;
+        regs.hours = (total / 3600 % 24) as u8;
+        regs.days_low = days as u8;
+        regs.days_high = (regs.days_high & !DAY_HIGH_BIT) | (days >> 8) as u8;
+    }
+
+    // the halt bit of the day high register
+    pub fn set_halted(&mut self, halted: bool) {
+        if halted {
+            self.current.days_high |= HALT_BIT;
+        } else {
+            self.current.days_high &= !HALT_BIT;
+        }
+    }
+
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.latched = self.current;
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        let regs = &self.latched;
+        match register {
+            0x08 => regs.seconds | 0xC0,
+            0x09 => regs.minutes | 0xC0,
+            0x0A => regs.hours | 0xE0,
+            0x0B => regs.days_low,
+            _ => regs.days_high | 0x3E,
+        }
+    }
+
+    pub fn write(&mut self, register: u8, value: u8) {
+        let regs = &mut self.current;
+        match register {
+            0x08 => {
+                regs.seconds = value & 0x3F;
+                self.cycles = 0; // writing the seconds resets the sub second divider
+            }
+            0x09 => regs.minutes = value & 0x3F,
+            0x0A => regs.hours = value & 0x1F,
+            0x0B => regs.days_low = value,
+            _ => regs.days_high = value & (DAY_HIGH_BIT | HALT_BIT | CARRY_BIT),
+        }
+    }
+
+    pub fn state(&self) -> RtcState {
+        RtcState {
+            current: self.current,
+            latched: self.latched,
+            timestamp: unix_now(),
+        }
+    }
+
+    // restore a saved clock, elapsed_seconds is how long the host was away (0 to ignore host time)
+    pub fn load_state(&mut self, state: &RtcState, elapsed_seconds: u64) {
+        self.current = state.current;
+        self.latched = state.latched;
+        self.cycles = 0;
+        self.advance_seconds(elapsed_seconds);
+    }
+
+    fn halted(&self) -> bool {
+        self.current.days_high & HALT_BIT != 0
+    }
+
+    fn in_range(&self) -> bool {
+        self.current.seconds < 60 && self.current.minutes < 60 && self.current.hours < 24
+    }
+
+    fn tick_second(&mut self) {
+        let regs = &mut self.current;
+        regs.seconds = (regs.seconds + 1) & 0x3F;
+        if regs.seconds != 60 {
+            return;
+        }
+        regs.seconds = 0;
+        regs.minutes = (regs.minutes + 1) & 0x3F;
+        if regs.minutes != 60 {
+            return;
+        }
+        regs.minutes = 0;
+        regs.hours = (regs.hours + 1) & 0x1F;
+        if regs.hours != 24 {
+            return;
+        }
+        regs.hours = 0;
+        let (days_low, overflow) = regs.days_low.overflowing_add(1);
+        regs.days_low = days_low;
+        if overflow {
+            if regs.days_high & DAY_HIGH_BIT != 0 {
+                regs.days_high = (regs.days_high & !DAY_HIGH_BIT) | CARRY_BIT;
+            } else {
+                regs.days_high |= DAY_HIGH_BIT;
+            }
+        }
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// seconds between a saved timestamp and now, a clock that went backwards counts as no time
+pub fn elapsed_since(timestamp: u64) -> u64 {
+    unix_now().saturating_sub(timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rtc(current: RtcRegisters) -> Rtc {
+        let mut rtc = Rtc::new();
+        rtc.load_state(&RtcState { current, ..Default::default() }, 0);
+        rtc
+    }
+
+    #[test]
+    fn seconds_carry_into_the_day_counter() {
+        let mut rtc = rtc(RtcRegisters { seconds: 59, minutes: 59, hours: 23, days_low: 0xFF, days_high: 0 });
+        rtc.tick(CYCLES_PER_SECOND);
+        assert_eq!(rtc.state().current, RtcRegisters { days_high: DAY_HIGH_BIT, ..Default::default() });
+    }
+
+    #[test]
+    fn host_time_catches_up_and_overflows_at_512_days() {
+        let mut rtc = rtc(RtcRegisters { days_low: 0xFF, days_high: DAY_HIGH_BIT, ..Default::default() });
+        rtc.advance_seconds(SECONDS_PER_DAY + 3661);
+        assert_eq!(
+            rtc.state().current,
+            RtcRegisters { seconds: 1, minutes: 1, hours: 1, days_low: 0, days_high: CARRY_BIT }
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_count_up_to_63_first() {
+        let mut rtc = rtc(RtcRegisters { seconds: 62, ..Default::default() });
+        rtc.advance_seconds(3);
+        assert_eq!((rtc.state().current.seconds, rtc.state().current.minutes), (1, 0));
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let mut rtc = rtc(RtcRegisters { days_high: HALT_BIT, ..Default::default() });
+        rtc.tick(CYCLES_PER_SECOND * 2);
+        rtc.advance_seconds(100);
+        assert_eq!(rtc.state().current.seconds, 0);
+        rtc.write(0x0C, 0);
+        rtc.tick(CYCLES_PER_SECOND);
+        assert_eq!(rtc.state().current.seconds, 1);
+    }
+}
@@ -0,0 +1,342 @@
+/* Battery backed saves
+   The .sav next to the rom holds whatever the cart's save_data gives back, which for most
+   carts is the raw external ram. That's the layout every other emulator uses too, so
+   saves can be moved between them as they are.
+
+   MBC3, HuC3 and TAMA5 carts with a clock get it appended after the ram in the 48 byte
+   trailer VBA-M and BGB write, all little endian:
+     0x00  current seconds, minutes, hours, days low, days high, a u32 each
+     0x14  latched seconds, minutes, hours, days low, days high
+     0x28  unix timestamp of the save, u64 (some older saves have a u32 here, 44 bytes total)
+   TAMA5's clock counts in the same registers as MBC3's (see tama5.rs). HuC3's clock and
+   alarm are turned into them as described in rtc.rs.
+   When a save is loaded the clock catches up on the host time that passed since then.
+
+   Changed saves are written every few seconds and as soon as the game switches the save
+   memory off, which games do right after saving, so a save survives the emulator being
+   killed before it gets to flush on the way out.
+
+   Writes go to a temporary file first which is then renamed over the old save, so a
+   crash halfway through leaves the previous save in place instead of half of a new one.
+*/
+use std::fs;
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::cartridge::mapper::Cartridge;
+use crate::cartridge::rtc::{self, ClockState, HuC3Clock, RtcRegisters, RtcState};
+
+// how often changed ram gets written out, in host time so it doesn't depend on emulation speed
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+// the host clock is only looked at once a frame, reading it every instruction is slow
+const CYCLES_PER_FRAME: u64 = 70224;
+
+pub const RTC_TRAILER_SIZE: usize = 48;
+const RTC_TRAILER_SIZE_32: usize = 44; // with a 32 bit timestamp
+
+pub fn save_path(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("sav")
+}
+
+// None when there's no save yet
+pub fn read(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+    let mut file = fs::File::create(&temp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp_path, path)
+}
+
+pub fn encode_rtc(state: &RtcState) -> Vec<u8> {
+    let mut data = Vec::with_capacity(RTC_TRAILER_SIZE);
+    for regs in [&state.current, &state.latched] {
+        for value in [regs.seconds, regs.minutes, regs.hours, regs.days_low, regs.days_high] {
+            data.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+    }
+    data.extend_from_slice(&state.timestamp.to_le_bytes());
+    data
+}
+
+// takes the whole trailer, 48 or 44 bytes
+pub fn decode_rtc(data: &[u8]) -> Option<RtcState> {
+    if data.len() != RTC_TRAILER_SIZE && data.len() != RTC_TRAILER_SIZE_32 {
+        return None;
+    }
+    let word = |index: usize| {
+        let bytes = &data[index * 4..index * 4 + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u8
+    };
+    let registers = |first: usize| RtcRegisters {
+        seconds: word(first),
+        minutes: word(first + 1),
+        hours: word(first + 2),
+        days_low: word(first + 3),
+        days_high: word(first + 4),
+    };
+    let mut timestamp = [0; 8];
+    timestamp[..data.len() - 0x28].copy_from_slice(&data[0x28..]);
+    Some(RtcState {
+        current: registers(0),
+        latched: registers(5),
+        timestamp: u64::from_le_bytes(timestamp),
+    })
+}
+
+fn encode_clock(state: &ClockState) -> Vec<u8> {
+    match state {
+        ClockState::Mbc3(state) => encode_rtc(state),
+        ClockState::HuC3(clock) => encode_rtc(&clock.to_rtc_state()),
+    }
+}
+
+// reads a trailer for the kind of clock the cart has, which `like` is an example of
+fn decode_clock(like: &ClockState, data: &[u8]) -> Option<ClockState> {
+    let state = decode_rtc(data)?;
+    Some(match like {
+        ClockState::Mbc3(_) => ClockState::Mbc3(state),
+        ClockState::HuC3(_) => ClockState::HuC3(HuC3Clock::from_rtc_state(&state)),
+    })
+}
+
+/* The cart's ram followed by the clock trailer if it has one, stamped with timestamp.
+   The timestamp moves every second, so changes are looked for with it at 0 and the real
+   one only goes in when the save is written. */
+fn cartridge_data(cartridge: &dyn Cartridge, timestamp: u64) -> Option<Vec<u8>> {
+    let ram = cartridge.save_data();
+    match cartridge.rtc_state() {
+        Some(state) => {
+            let mut data = ram.unwrap_or_default();
+            data.extend_from_slice(&encode_clock(&state.with_timestamp(timestamp)));
+            Some(data)
+        }
+        None => ram,
+    }
+}
+
+/* Splits a save into the ram and the clock. Saves without a trailer (written by an emulator
+   that didn't keep the clock, or from before the cart had one) load as plain ram. */
+fn load_into(cartridge: &mut dyn Cartridge, data: &[u8]) {
+    if let Some(clock) = cartridge.rtc_state() {
+        let ram_size = cartridge.save_data().map_or(0, |ram| ram.len());
+        if let Some(state) = data.get(ram_size..).and_then(|trailer| decode_clock(&clock, trailer)) {
+            cartridge.load_save_data(&data[..ram_size]);
+            cartridge.load_rtc_state(&state, rtc::elapsed_since(state.timestamp()));
+            return;
+        }
+    }
+    cartridge.load_save_data(data);
+}
+
+pub struct SaveFile {
+    path: PathBuf,
+    written: Vec<u8>, // what's on disk without its timestamp, so unchanged ram isn't written again
+    last_flush: Instant,
+    ram_enabled: bool, // as of the last tick
+    next_check: u64, // cycle count at which the host clock gets looked at again
+}
+
+impl SaveFile {
+    // loads an existing save into the cart
+    pub fn open(rom_path: &Path, cartridge: &mut dyn Cartridge) -> Result<Self, Error> {
+        let path = save_path(rom_path);
+        let mut written = Vec::new();
+        if let Some(data) = read(&path)? {
+            load_into(cartridge, &data);
+            // a clock that caught up on the time away is still the one on disk
+            written = cartridge_data(cartridge, 0).unwrap_or(data);
+        }
+        Ok(SaveFile { path, written, last_flush: Instant::now(), ram_enabled: cartridge.ram_enabled(), next_check: 0 })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /* Call after every instruction with the bus cycle count, writes the save every FLUSH_INTERVAL
+       and when the game switches ram off, if it changed. Games can switch it on and off again
+       within a frame, so that's checked every call. */
+    pub fn tick(&mut self, cartridge: &dyn Cartridge, cycles: u64) -> Result<(), Error> {
+        let was_enabled = std::mem::replace(&mut self.ram_enabled, cartridge.ram_enabled());
+        let switched_off = was_enabled && !self.ram_enabled;
+        if !switched_off {
+            if cycles < self.next_check {
+                return Ok(());
+            }
+            self.next_check = cycles + CYCLES_PER_FRAME;
+            if self.last_flush.elapsed() < FLUSH_INTERVAL {
+                return Ok(());
+            }
+        }
+        self.last_flush = Instant::now();
+        self.flush(cartridge)
+    }
+
+    pub fn flush(&mut self, cartridge: &dyn Cartridge) -> Result<(), Error> {
+        let Some(data) = cartridge_data(cartridge, 0) else {
+            return Ok(());
+        };
+        if data == self.written {
+            return Ok(());
+        }
+        if let Some(stamped) = cartridge_data(cartridge, rtc::unix_now()) {
+            write_atomic(&self.path, &stamped)?;
+        }
+        self.written = data;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::huc3::HuC3;
+    use crate::cartridge::mbc3::Mbc3;
+
+    // a rom path in a directory of its own, so saves from different tests don't meet
+    fn rom_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("emulator-save-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("game.gb")
+    }
+
+    const RAM_SIZE: usize = 0x2000;
+
+    fn mbc3(has_rtc: bool) -> Mbc3 {
+        let mut cart = Mbc3::new(vec![0; 0x8000], RAM_SIZE, has_rtc);
+        cart.write_rom(0x0000, 0x0A);
+        cart
+    }
+
+    #[test]
+    fn rtc_trailer_round_trip() {
+        let state = RtcState {
+            current: RtcRegisters { seconds: 1, minutes: 2, hours: 3, days_low: 4, days_high: 0xC1 },
+            latched: RtcRegisters { seconds: 5, minutes: 6, hours: 7, days_low: 8, days_high: 0 },
+            timestamp: 0x1_2345_6789,
+        };
+        let data = encode_rtc(&state);
+        assert_eq!(data.len(), RTC_TRAILER_SIZE);
+        assert_eq!(decode_rtc(&data), Some(state));
+        // the older 44 byte trailer has a 32 bit timestamp
+        let short = decode_rtc(&data[..RTC_TRAILER_SIZE_32]).unwrap();
+        assert_eq!(short.timestamp, 0x2345_6789);
+        assert_eq!(decode_rtc(&data[..40]), None);
+    }
+
+    #[test]
+    fn ram_and_clock_come_back() {
+        let path = rom_path("round-trip");
+        let mut cart = mbc3(true);
+        cart.write_ram(0xA010, 0x42);
+        cart.write_rom(0x4000, 0x09); // minutes
+        cart.write_ram(0xA000, 17);
+        let mut file = SaveFile::open(&path, &mut cart).unwrap();
+        file.flush(&cart).unwrap();
+        assert_eq!(fs::read(save_path(&path)).unwrap().len(), RAM_SIZE + RTC_TRAILER_SIZE);
+
+        let mut loaded = mbc3(true);
+        SaveFile::open(&path, &mut loaded).unwrap();
+        assert_eq!(loaded.save_data().unwrap()[0x10], 0x42);
+        let Some(ClockState::Mbc3(state)) = loaded.rtc_state() else { panic!("no MBC3 clock") };
+        assert_eq!(state.current.minutes, 17);
+        assert!(!save_path(&path).with_extension("sav.tmp").exists());
+    }
+
+    #[test]
+    fn huc3_clock_and_alarm_go_in_the_trailer() {
+        let path = rom_path("huc3");
+        let mut cart = HuC3::new(vec![0; 0x8000], RAM_SIZE);
+        let mut file = SaveFile::open(&path, &mut cart).unwrap();
+        // the clock at minute 0x59A of day 0x123, the alarm at minute 0x123 and armed,
+        // through the rtc command port
+        cart.write_rom(0x0000, 0x0B);
+        for command in [0x40, 0x50, 0x3A, 0x39, 0x35, 0x33, 0x32, 0x31,
+                        0x48, 0x55, 0x33, 0x32, 0x31, 0x4F, 0x21] {
+            cart.write_ram(0xA000, command);
+        }
+        file.flush(&cart).unwrap();
+        let data = fs::read(save_path(&path)).unwrap();
+        assert_eq!(data.len(), RAM_SIZE + RTC_TRAILER_SIZE);
+        let state = decode_rtc(&data[RAM_SIZE..]).unwrap();
+        assert_eq!(state.current, RtcRegisters { seconds: 0, minutes: 54, hours: 23, days_low: 0x23, days_high: 0x01 });
+        assert_eq!(state.latched, RtcRegisters { seconds: 1, minutes: 51, hours: 4, days_low: 0, days_high: 0 });
+
+        let mut loaded = HuC3::new(vec![0; 0x8000], RAM_SIZE);
+        SaveFile::open(&path, &mut loaded).unwrap();
+        let unstamped = |cart: &HuC3| cart.rtc_state().map(|state| state.with_timestamp(0));
+        assert_eq!(unstamped(&loaded), unstamped(&cart));
+    }
+
+    #[test]
+    fn plain_ram_save_loads_into_a_clock_cart() {
+        let path = rom_path("no-trailer");
+        let mut ram = vec![0; RAM_SIZE];
+        ram[3] = 0x99;
+        write_atomic(&save_path(&path), &ram).unwrap();
+        let mut cart = mbc3(true);
+        SaveFile::open(&path, &mut cart).unwrap();
+        assert_eq!(cart.save_data().unwrap()[3], 0x99);
+    }
+
+    #[test]
+    fn switching_ram_off_writes_the_save() {
+        let path = rom_path("switched-off");
+        let mut cart = mbc3(false);
+        let mut file = SaveFile::open(&path, &mut cart).unwrap();
+        cart.write_ram(0xA000, 0x01);
+        file.tick(&cart, 4).unwrap();
+        assert!(!save_path(&path).exists());
+        cart.write_rom(0x0000, 0x00);
+        file.tick(&cart, 8).unwrap();
+        assert_eq!(fs::read(save_path(&path)).unwrap()[0], 0x01);
+    }
+
+    #[test]
+    fn the_host_clock_is_checked_once_a_frame() {
+        let path = rom_path("once-a-frame");
+        let mut cart = mbc3(false);
+        let mut file = SaveFile::open(&path, &mut cart).unwrap();
+        cart.write_ram(0xA000, 0x01);
+        file.tick(&cart, 0).unwrap();
+        file.last_flush -= FLUSH_INTERVAL;
+        file.tick(&cart, CYCLES_PER_FRAME - 4).unwrap();
+        assert!(!save_path(&path).exists());
+        file.tick(&cart, CYCLES_PER_FRAME).unwrap();
+        assert_eq!(fs::read(save_path(&path)).unwrap()[0], 0x01);
+    }
+
+    #[test]
+    fn unchanged_saves_are_not_written_again() {
+        for has_rtc in [false, true] {
+            let path = rom_path(if has_rtc { "unchanged-rtc" } else { "unchanged" });
+            let mut cart = mbc3(has_rtc);
+            cart.write_ram(0xA000, 0x01);
+            let mut file = SaveFile::open(&path, &mut cart).unwrap();
+            file.flush(&cart).unwrap();
+            fs::remove_file(save_path(&path)).unwrap();
+            if has_rtc {
+                // the timestamp in the trailer has moved on, nothing else has
+                std::thread::sleep(Duration::from_millis(1100));
+            }
+            file.flush(&cart).unwrap();
+            assert!(!save_path(&path).exists());
+            cart.write_ram(0xA000, 0x02);
+            file.flush(&cart).unwrap();
+            assert!(save_path(&path).exists());
+        }
+    }
+}
@@ -0,0 +1,214 @@
+/* Bandai TAMA5 (Tamagotchi 3)
+   Everything goes through two addresses, following the register interface mGBA emulates:
+   0xA001  write picks a register, reads 0xFF
+   0xA000  write puts the low nibble in the picked register, read gives the picked output
+   Registers (write):
+     0  rom bank, low nibble
+     1  rom bank, bit 4
+     4  data, low nibble
+     5  data, high nibble
+     6  bit 0 is bit 4 of the address, bits 1-3 the command
+     7  address, low nibble, writing it runs the command
+   Outputs (read):
+     A  0xF1, the chip is always ready
+     C  0xF0 | low nibble of the result
+     D  0xF0 | high nibble of the result
+   Commands:
+     0  write the data to byte `address` of the 32 byte ram
+     1  read byte `address` of the ram into the result
+     2  clock, the address picks what to do with it:
+          0 / 1  stop / start the clock
+          4 / 5  set the minutes / hours from the data, in BCD
+          6 / 7  read the minutes / hours into the result, in BCD
+
+   The clock counts like MBC3's and is saved in the same trailer; stopping it sets the halt bit.
+*/
+use crate::cartridge::mapper::{banked_read, load_ram, Cartridge, ROM_BANK_SIZE};
+use crate::cartridge::rtc::{ClockState, Rtc};
+
+const RAM_SIZE: usize = 32;
+
+pub struct Tama5 {
+    rom: Vec<u8>,
+    ram: [u8; RAM_SIZE],
+    rtc: Rtc,
+    select: u8, // register picked through 0xA001
+    registers: [u8; 8],
+    result: u8,
+}
+
+impl Tama5 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Tama5 {
+            rom,
+            ram: [0; RAM_SIZE],
+            rtc: Rtc::new(),
+            select: 0,
+            registers: [0; 8],
+            result: 0,
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        (self.registers[0] | ((self.registers[1] & 0x01) << 4)) as usize
+    }
+
+    fn command(&mut self) {
+        let address = (((self.registers[6] & 0x01) << 4) | self.registers[7]) as usize;
+        let data = self.registers[4] | (self.registers[5] << 4);
+        match self.registers[6] >> 1 {
+            0 => self.ram[address] = data,
+            1 => self.result = self.ram[address],
+            2 => self.clock(address, data),
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self, command: usize, data: u8) {
+        match command {
+            0 => self.rtc.set_halted(true),
+            1 => self.rtc.set_halted(false),
+            4 => self.rtc.write(0x09, from_bcd(data)),
+            5 => self.rtc.write(0x0A, from_bcd(data)),
+            6 | 7 => {
+                self.rtc.write_latch(0x00);
+                self.rtc.write_latch(0x01);
+                let value = if command == 6 { self.rtc.read(0x09) & 0x3F } else { self.rtc.read(0x0A) & 0x1F };
+                self.result = to_bcd(value);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+impl Cartridge for Tama5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank(), (address - 0x4000) as usize),
+        }
+    }
+
+    fn write_rom(&mut self, _address: u16, _value: u8) {}
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if address & 0x01 != 0 {
+            return 0xFF;
+        }
+        match self.select {
+            0x0A => 0xF1,
+            0x0C => 0xF0 | (self.result & 0x0F),
+            0x0D => 0xF0 | (self.result >> 4),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if address & 0x01 != 0 {
+            self.select = value & 0x0F;
+            return;
+        }
+        if let Some(register) = self.registers.get_mut(self.select as usize) {
+            *register = value & 0x0F;
+            if self.select == 7 {
+                self.command();
+            }
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.rtc.tick(cycles);
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.ram.to_vec())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data)
+    }
+
+    fn rtc_state(&self) -> Option<ClockState> {
+        Some(ClockState::Mbc3(self.rtc.state()))
+    }
+
+    fn load_rtc_state(&mut self, state: &ClockState, elapsed_seconds: u64) {
+        if let ClockState::Mbc3(state) = state {
+            self.rtc.load_state(state, elapsed_seconds);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::mapper::numbered_banks;
+    use crate::cartridge::rtc::CYCLES_PER_SECOND;
+
+    fn write(cart: &mut Tama5, register: u8, value: u8) {
+        cart.write_ram(0xA001, register);
+        cart.write_ram(0xA000, value);
+    }
+
+    fn run(cart: &mut Tama5, command: u8, address: u8, data: u8) {
+        write(cart, 4, data & 0x0F);
+        write(cart, 5, data >> 4);
+        write(cart, 6, (command << 1) | (address >> 4));
+        write(cart, 7, address & 0x0F);
+    }
+
+    fn result(cart: &mut Tama5) -> u8 {
+        cart.write_ram(0xA001, 0x0C);
+        let low = cart.read_ram(0xA000) & 0x0F;
+        cart.write_ram(0xA001, 0x0D);
+        low | ((cart.read_ram(0xA000) & 0x0F) << 4)
+    }
+
+    #[test]
+    fn rom_bank_is_split_over_two_registers() {
+        let mut cart = Tama5::new(numbered_banks(32));
+        write(&mut cart, 0, 0x03);
+        write(&mut cart, 1, 0x01);
+        assert_eq!(cart.read_rom(0x4000), 0x13);
+    }
+
+    #[test]
+    fn ram_is_written_and_read_a_nibble_at_a_time() {
+        let mut cart = Tama5::new(numbered_banks(2));
+        cart.write_ram(0xA001, 0x0A);
+        assert_eq!(cart.read_ram(0xA000), 0xF1);
+        run(&mut cart, 0, 0x1F, 0xA5);
+        run(&mut cart, 1, 0x1F, 0);
+        assert_eq!(result(&mut cart), 0xA5);
+        assert_eq!(cart.save_data().unwrap()[0x1F], 0xA5);
+    }
+
+    #[test]
+    fn the_clock_is_set_and_read_in_bcd() {
+        let mut cart = Tama5::new(numbered_banks(2));
+        run(&mut cart, 2, 5, 0x23);
+        run(&mut cart, 2, 4, 0x59);
+        cart.tick(CYCLES_PER_SECOND * 60);
+        run(&mut cart, 2, 6, 0);
+        assert_eq!(result(&mut cart), 0x00);
+        run(&mut cart, 2, 7, 0);
+        assert_eq!(result(&mut cart), 0x00);
+        // a stopped clock doesn't move
+        run(&mut cart, 2, 0, 0);
+        cart.tick(CYCLES_PER_SECOND * 60);
+        run(&mut cart, 2, 6, 0);
+        assert_eq!(result(&mut cart), 0x00);
+        run(&mut cart, 2, 1, 0);
+        cart.tick(CYCLES_PER_SECOND * 60);
+        run(&mut cart, 2, 6, 0);
+        assert_eq!(result(&mut cart), 0x01);
+    }
+}
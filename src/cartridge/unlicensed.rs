@@ -0,0 +1,270 @@
+/* Mappers from unlicensed carts. None of them identify themselves in the header's cartridge
+   type, so they're picked by an override from the host. Wisdom Tree carts are also found by
+   their name in the rom when the header can't be the real one (see mapper::load_with_override).
+   There's no text we know to be in every Sachen dump, so those always need the override.
+
+   Wisdom Tree
+     32 KiB banks at 0x0000 - 0x7FFF. A write anywhere in 0x0000 - 0x3FFF selects the bank
+     given by the low byte of the address (the written value is ignored).
+
+   Sachen MMC1 / MMC2
+     0x0000 - 0x1FFF  base bank, only writable while bits 4-5 of the rom bank are set
+     0x2000 - 0x3FFF  rom bank (0 reads as 1)
+     0x4000 - 0x5FFF  bank mask, bits set in the mask come from the base bank
+     0x0000 - 0x3FFF shows base & mask, 0x4000 - 0x7FFF shows (base & mask) | (bank & !mask).
+     At power on the cart is locked and reads from 0x0100 - 0x01FF have address lines
+     scrambled, so the boot rom's first pass over the logo shows Sachen's own logo while the
+     second pass (the comparison) sees nintendo's. MMC1 unlocks after the first pass.
+     MMC2 also sets A7 during a second locked stage meant for the CGB boot rom and unlocks
+     after that one.
+*/
+use crate::cartridge::mapper::{banked_read, Cartridge, ROM_BANK_SIZE};
+use std::cell::Cell;
+
+const WISDOM_TREE_BANK_SIZE: usize = 0x8000;
+const LOGO_START: u16 = 0x0104;
+const LOGO_END: u16 = 0x0133;
+const LOGO_READS_PER_PASS: u32 = 0x30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnlicensedMapper {
+    WisdomTree,
+    SachenMmc1,
+    SachenMmc2,
+}
+
+impl UnlicensedMapper {
+    // the names the host can use to force a mapper
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wisdom-tree" => Some(UnlicensedMapper::WisdomTree),
+            "sachen-mmc1" => Some(UnlicensedMapper::SachenMmc1),
+            "sachen-mmc2" => Some(UnlicensedMapper::SachenMmc2),
+            _ => None,
+        }
+    }
+}
+
+// look for the publisher's name in the first 32 KiB, where Wisdom Tree puts it
+pub fn detect(rom: &[u8]) -> Option<UnlicensedMapper> {
+    let start = &rom[..rom.len().min(0x8000)];
+    let contains = |needle: &[u8]| start.windows(needle.len()).any(|window| window == needle);
+    if contains(b"WISDOM TREE") || contains(b"WISDOM\x00TREE") {
+        Some(UnlicensedMapper::WisdomTree)
+    } else {
+        None
+    }
+}
+
+pub fn load(rom: Vec<u8>, mapper: UnlicensedMapper) -> Box<dyn Cartridge> {
+    match mapper {
+        UnlicensedMapper::WisdomTree => Box::new(WisdomTree::new(rom)),
+        UnlicensedMapper::SachenMmc1 => Box::new(Sachen::booted(rom, false)),
+        UnlicensedMapper::SachenMmc2 => Box::new(Sachen::booted(rom, true)),
+    }
+}
+
+pub struct WisdomTree {
+    rom: Vec<u8>,
+    bank: u8,
+}
+
+impl WisdomTree {
+    pub fn new(rom: Vec<u8>) -> Self {
+        WisdomTree { rom, bank: 0 }
+    }
+}
+
+impl Cartridge for WisdomTree {
+    fn read_rom(&self, address: u16) -> u8 {
+        banked_read(&self.rom, WISDOM_TREE_BANK_SIZE, self.bank as usize, address as usize)
+    }
+
+    fn write_rom(&mut self, address: u16, _value: u8) {
+        if address < 0x4000 {
+            self.bank = address as u8;
+        }
+    }
+
+    fn read_ram(&self, _address: u16) -> u8 {
+        0xFF
+    }
+
+    fn write_ram(&mut self, _address: u16, _value: u8) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SachenLock {
+    Dmg,
+    Cgb, // MMC2 only
+    Unlocked,
+}
+
+pub struct Sachen {
+    rom: Vec<u8>,
+    mmc2: bool,
+    base: u8,
+    bank: u8,
+    mask: u8,
+    lock: Cell<SachenLock>, // reads move the lock along, hence the cells
+    logo_reads: Cell<u32>,
+}
+
+impl Sachen {
+    // locked, the way it powers on
+    pub fn new(rom: Vec<u8>, mmc2: bool) -> Self {
+        Sachen {
+            rom,
+            mmc2,
+            base: 0,
+            bank: 1,
+            mask: 0,
+            lock: Cell::new(SachenLock::Dmg),
+            logo_reads: Cell::new(0),
+        }
+    }
+
+    // there's no boot rom yet, so its passes over the logo are done here to leave the cart unlocked
+    pub fn booted(rom: Vec<u8>, mmc2: bool) -> Self {
+        let cart = Sachen::new(rom, mmc2);
+        while cart.lock.get() != SachenLock::Unlocked {
+            for address in LOGO_START..=LOGO_END {
+                cart.read_rom(address);
+            }
+        }
+        cart
+    }
+
+    fn count_logo_read(&self, address: u16) {
+        if !(LOGO_START..=LOGO_END).contains(&address) {
+            return;
+        }
+        let reads = self.logo_reads.get() + 1;
+        self.logo_reads.set(reads);
+        if reads < LOGO_READS_PER_PASS {
+            return;
+        }
+        self.logo_reads.set(0);
+        self.lock.set(match self.lock.get() {
+            SachenLock::Dmg if self.mmc2 => SachenLock::Cgb,
+            _ => SachenLock::Unlocked,
+        });
+    }
+}
+
+// swaps A0 with A6 and A1 with A4, which is how the locked cart wires the low address lines
+fn unscramble(address: u16) -> u16 {
+    (address & 0xFFAC)
+        | ((address & 0x40) >> 6)
+        | ((address & 0x10) >> 3)
+        | ((address & 0x02) << 3)
+        | ((address & 0x01) << 6)
+}
+
+impl Cartridge for Sachen {
+    fn read_rom(&self, address: u16) -> u8 {
+        let lock = self.lock.get();
+        let mut mapped = address;
+        if lock != SachenLock::Unlocked && (address & 0xFF00) == 0x0100 {
+            if lock == SachenLock::Cgb {
+                mapped |= 0x80;
+            }
+            mapped = unscramble(mapped);
+        }
+        if lock != SachenLock::Unlocked {
+            self.count_logo_read(address);
+        }
+        let base = self.base & self.mask;
+        match mapped {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, base as usize, mapped as usize),
+            _ => {
+                let bank = base | (self.bank & !self.mask);
+                banked_read(&self.rom, ROM_BANK_SIZE, bank as usize, (mapped - 0x4000) as usize)
+            }
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF if self.bank & 0x30 == 0x30 => self.base = value,
+            0x2000..=0x3FFF => self.bank = if value == 0 { 1 } else { value },
+            0x4000..=0x5FFF => self.mask = value,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, _address: u16) -> u8 {
+        0xFF
+    }
+
+    fn write_ram(&mut self, _address: u16, _value: u8) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 64 banks, every byte holding its bank number, with markers where the locked reads go
+    fn sachen_rom() -> Vec<u8> {
+        let mut rom: Vec<u8> = (0..64 * ROM_BANK_SIZE).map(|i| (i / ROM_BANK_SIZE) as u8).collect();
+        rom[0x0105] = 0x55;
+        rom[0x0144] = 0xAA; // 0x0105 with A0 and A6 swapped
+        rom[0x01C4] = 0x77; // the same with A7 set as well
+        rom
+    }
+
+    fn logo_pass(cart: &Sachen) {
+        for address in LOGO_START..=LOGO_END {
+            cart.read_rom(address);
+        }
+    }
+
+    #[test]
+    fn mmc1_starts_locked_and_unlocks_after_one_logo_pass() {
+        let cart = Sachen::new(sachen_rom(), false);
+        assert_eq!(cart.read_rom(0x0105), 0xAA);
+        logo_pass(&cart);
+        assert_eq!(cart.read_rom(0x0105), 0x55);
+    }
+
+    #[test]
+    fn mmc2_has_a_second_locked_stage_for_the_cgb() {
+        let cart = Sachen::new(sachen_rom(), true);
+        assert_eq!(cart.read_rom(0x0105), 0xAA);
+        logo_pass(&cart);
+        assert_eq!(cart.read_rom(0x0105), 0x77);
+        logo_pass(&cart);
+        assert_eq!(cart.read_rom(0x0105), 0x55);
+    }
+
+    #[test]
+    fn loaded_without_a_boot_rom_it_comes_up_unlocked() {
+        for mapper in [UnlicensedMapper::SachenMmc1, UnlicensedMapper::SachenMmc2] {
+            assert_eq!(load(sachen_rom(), mapper).read_rom(0x0105), 0x55);
+        }
+    }
+
+    #[test]
+    fn sachen_base_and_mask() {
+        let mut cart = Sachen::booted(sachen_rom(), false);
+        cart.write_rom(0x2000, 0x30);
+        cart.write_rom(0x0000, 0x04);
+        cart.write_rom(0x4000, 0x0C);
+        cart.write_rom(0x2000, 0x31);
+        assert_eq!(cart.read_rom(0x0000), 0x04);
+        assert_eq!(cart.read_rom(0x4000), 0x35);
+        // the base only takes writes while bits 4-5 of the bank are set
+        cart.write_rom(0x2000, 0x00);
+        cart.write_rom(0x0000, 0x08);
+        assert_eq!(cart.read_rom(0x0000), 0x04);
+        assert_eq!(cart.read_rom(0x4000), 0x05); // bank 0 reads as 1
+    }
+
+    #[test]
+    fn wisdom_tree_banks_on_the_address() {
+        let rom: Vec<u8> = (0..4 * WISDOM_TREE_BANK_SIZE).map(|i| (i / WISDOM_TREE_BANK_SIZE) as u8).collect();
+        let mut cart = WisdomTree::new(rom);
+        cart.write_rom(0x0002, 0xFF);
+        assert_eq!((cart.read_rom(0x0000), cart.read_rom(0x7FFF)), (2, 2));
+    }
+}
@@ -1,5 +1,8 @@
+use crate::cartridge::mapper::Cartridge;
 use crate::cpu_core::instruction::*;
+use crate::cpu_core::oam_bug::OamBugKind;
 use crate::cpu_core::registers::RegisterBank;
+use crate::cpu_core::timing;
 use crate::{cpu_core::memory::MemoryBus, cpu_core::registers::RegistersU16, cpu_core::registers::RegistersU8, cpu_core::flags_register::Flags};
 
 pub struct CPU {
@@ -8,7 +11,26 @@ pub struct CPU {
     sp: u16, // stack pointer
     bus: MemoryBus,
     is_halted: bool,
+    is_locked: bool, // set by an illegal opcode, only a power cycle clears it
+    is_stopped: bool, // set by STOP, a button press clears it
+    events: Vec<CpuEvent>,
+    branch_taken: bool, // set by the last conditional jump/call/return, for timing
 }
+
+/* Things the cpu wants the host (frontend, debugger) to know about.
+   They queue up until the host drains them with take_events */
+#[derive(Debug, Clone, PartialEq)]
+pub enum CpuEvent {
+    // an illegal opcode was executed at pc, the cpu is hung from here on
+    Lockup { pc: u16, opcode: u8 },
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CPU {
     pub fn new() -> Self {
         CPU {
@@ -17,22 +39,67 @@ impl CPU {
             sp: 0,
             bus: MemoryBus::new(),
             is_halted: false,
+            is_locked: false,
+            is_stopped: false,
+            events: Vec::new(),
+            branch_taken: false,
         }
     }
 
+    pub fn is_locked(&self) -> bool {
+        self.is_locked
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.is_stopped
+    }
+
+    pub fn take_events(&mut self) -> Vec<CpuEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn bus(&self) -> &MemoryBus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut MemoryBus {
+        &mut self.bus
+    }
+
     pub fn load_rom(&mut self, data: Vec<u8>) {
-        for (i, &byte) in data.iter().enumerate() {
-            self.bus.write_byte(i as u16, byte)
-        }
+        self.bus.load_rom(data)
+    }
+
+    pub fn load_cartridge(&mut self, cartridge: Box<dyn Cartridge>) {
+        self.bus.load_cartridge(cartridge)
     }
 
     pub fn step(&mut self) {
-        let mut instruction_byte = self.bus.read_byte(self.pc);
+        if self.is_locked {
+            // the cpu no longer fetches anything, but the rest of the system keeps running
+            self.bus.tick(4);
+            return;
+        }
+        if self.is_stopped {
+            // the system clock is off, nothing on the bus moves until a button is pressed
+            if !self.bus.joypad_pressed() {
+                return;
+            }
+            self.is_stopped = false;
+        }
+        // a VRAM DMA holds the cpu while it copies
+        let stall = self.bus.take_stall_cycles();
+        if stall > 0 {
+            self.bus.tick(stall);
+            return;
+        }
+        let mut instruction_byte = self.bus.fetch_byte(self.pc);
         let prefixed = instruction_byte == 0xCB; // 0xCB is the prefix byte
         if prefixed {
             // if we get a prefix byte we should read the next byte
-            instruction_byte = self.bus.read_byte(self.pc + 1);
+            instruction_byte = self.bus.fetch_byte(self.pc + 1);
         }
+        self.branch_taken = false;
         let next_pc: u16 = if let Some(instruction) = Instruction::from_byte(instruction_byte, prefixed)
         {
             println!("Executing instruction: 0x{instruction_byte:x}");
@@ -47,25 +114,40 @@ impl CPU {
         };
 
         self.pc = next_pc;
+        // everything else on the bus catches up once the instruction is done
+        let mut cycles = timing::cycles(instruction_byte, prefixed);
+        if self.branch_taken && !prefixed {
+            cycles += timing::branch_extra(instruction_byte);
+        }
+        self.bus.tick(cycles);
     }
 
     fn read_next_byte(&self) -> u8 {
-        self.bus.read_byte(self.pc + 1)
+        self.bus.fetch_byte(self.pc + 1)
     }
     fn read_next_word(&self) -> u16 {
         todo!()
     }
     fn push(&mut self, value: u16) {
+        // on DMG every step of a push in 0xFE00-0xFEFF corrupts OAM like a write
+        // the first step is an m-cycle of its own, before either write
+        self.bus.trigger_oam_bug(self.sp, OamBugKind::Write);
+        self.bus.internal_cycle();
         self.sp = self.sp.wrapping_sub(1);
+        self.bus.trigger_oam_bug(self.sp, OamBugKind::Write);
         self.bus.write_byte(self.sp, ((value & 0xFF00) >> 8) as u8);
 
         self.sp = self.sp.wrapping_sub(1);
+        self.bus.trigger_oam_bug(self.sp, OamBugKind::Write);
         self.bus.write_byte(self.sp, (value & 0xFF) as u8);
     }
     fn pop(&mut self) -> u16 {
+        // the first read shares its m-cycle with the sp increase, the second is a plain read
+        self.bus.trigger_oam_bug(self.sp, OamBugKind::ReadIncrease);
         let lsb = self.bus.read_byte(self.sp) as u16;
         self.sp = self.sp.wrapping_add(1);
 
+        self.bus.trigger_oam_bug(self.sp, OamBugKind::Read);
         let msb = self.bus.read_byte(self.sp) as u16;
         self.sp = self.sp.wrapping_add(1);
 
@@ -122,7 +204,6 @@ impl CPU {
             Instruction::PUSH(target) => {
                 let value = match target {
                     StackTarget::BC => self.registers.get_register_u16(RegistersU16::BC),
-                    _ => todo!("More Targets")
                 };
                 self.push(value);
                 self.pc.wrapping_add(1)
@@ -131,14 +212,25 @@ impl CPU {
                 let result = self.pop();
                 match target {
                     StackTarget::BC => self.registers.set_register_u16(RegistersU16::BC, result),
-                    _ => todo!("Support more instructions")
-                    
                 };
                 self.pc.wrapping_add(1)
             }
             Instruction::NOP => {
                 self.pc.wrapping_add(1)
             }
+            Instruction::ILLEGAL(opcode) => {
+                self.is_locked = true;
+                self.events.push(CpuEvent::Lockup { pc: self.pc, opcode });
+                self.pc
+            }
+            Instruction::STOP(_) => {
+                if self.bus.model().is_cgb() && self.bus.speed_switch_armed() {
+                    self.bus.switch_speed();
+                } else {
+                    self.is_stopped = true;
+                }
+                self.pc.wrapping_add(2)
+            }
             Instruction::HALT => {
                 self.is_halted = true;
                 panic!("reach halt instruction")
@@ -149,14 +241,22 @@ impl CPU {
                         let a = self.registers.get_register_u8(RegistersU8::A);
                         self.registers.set_register_u8(RegistersU8::A, a.wrapping_add(1))
                     } 
-                    IncDecTarget::DE => panic!("Inc for target DE not implemented"),
-                    IncDecTarget::BC => todo!("Inc for target BC not implemented"),
+                    IncDecTarget::DE => self.inc_dec_u16(RegistersU16::DE, true),
+                    IncDecTarget::BC => self.inc_dec_u16(RegistersU16::BC, true),
                     IncDecTarget::B => todo!("Inc for target B not implemented"),
                     IncDecTarget::C => todo!("Inc for target c is not implemented"),
                 };
                 self.pc.wrapping_add(1)
 
             }
+            Instruction::DEC(target) => {
+                match target {
+                    IncDecTarget::DE => self.inc_dec_u16(RegistersU16::DE, false),
+                    IncDecTarget::BC => self.inc_dec_u16(RegistersU16::BC, false),
+                    _ => todo!("Dec for 8 bit targets not implemented"),
+                };
+                self.pc.wrapping_add(1)
+            }
             _ => {
                 panic!("unsupported instruction encountered: {:?}", instruction);
             }
@@ -178,12 +278,13 @@ impl CPU {
         self.registers.set_flag(Flags::HALF_CARRY, (self.registers.get_register_u8(RegistersU8::A) & 0xF) + (value & 0xF) > 0xF);
         new_value
     }
-    fn jump(&self, should_jump: bool) -> u16 {
+    fn jump(&mut self, should_jump: bool) -> u16 {
+        self.branch_taken = should_jump;
         if should_jump {
             // Gameboy is little endian so read pc + 2 as most sig
             // and pc + 1 as least sig
-            let least_significant_byte = self.bus.read_byte(self.pc + 1) as u16;
-            let most_significant_byte = self.bus.read_byte(self.pc + 2) as u16;
+            let least_significant_byte = self.bus.fetch_byte(self.pc + 1) as u16;
+            let most_significant_byte = self.bus.fetch_byte(self.pc + 2) as u16;
             (most_significant_byte << 8) | least_significant_byte
         } else {
             // if we don't jump we need to still move the pc fwd by 3 to account for the jp instr
@@ -192,6 +293,7 @@ impl CPU {
     }
     fn call(&mut self, should_jump: bool) -> u16 {
         let next_pc = self.pc.wrapping_add(3);
+        self.branch_taken = should_jump;
         if should_jump {
             self.push(next_pc);
             self.read_next_word()
@@ -200,12 +302,25 @@ impl CPU {
         }
     }
     fn return_ (&mut self, should_jump: bool) -> u16 {
+        self.branch_taken = should_jump;
         if should_jump {
             self.pop()
         } else {
             self.pc.wrapping_add(1)
         }
     }
+    // 16 bit inc/dec doesn't touch the flags, but the address it puts on the bus can trip the OAM bug
+    fn inc_dec_u16(&mut self, register: RegistersU16, increment: bool) {
+        let value = self.registers.get_register_u16(register);
+        self.bus.trigger_oam_bug(value, OamBugKind::Write);
+        let new_value = if increment { value.wrapping_add(1) } else { value.wrapping_sub(1) };
+        self.registers.set_register_u16(register, new_value);
+    }
+}
+
+// stubs for the instructions that aren't wired up to execute yet
+#[allow(dead_code)]
+impl CPU {
     fn addhl(&mut self) {} // add to hl register
     fn adc(&mut self) {} // add with carry
     fn sub(&mut self) {} // subtract value with A register
@@ -234,7 +349,9 @@ impl CPU {
     fn sra(&mut self) {} // SRA (shift right arithmetic) - arithmetic shift a specific register right by 1
     fn sla(&mut self) {} // SLA (shift left arithmetic) - arithmetic shift a specific register left by 1
     fn swap(&mut self) {} // SWAP (swap nibbles) - switch upper and lower nibble of a specific register
+}
 
+impl CPU {
     fn handle_add(&mut self, target: ArithmeticTarget) -> u16 {
         let value = match target {
                     ArithmeticTarget::A => self.registers.get_register_u8(RegistersU8::A),
@@ -261,3 +378,65 @@ impl CPU {
         self.jump(jump_condition)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu_core::joypad::Button;
+    use crate::cpu_core::model::Model;
+
+    fn cpu_with(program: &[u8]) -> CPU {
+        let mut rom = vec![0; 0x8000];
+        rom[..program.len()].copy_from_slice(program);
+        let mut cpu = CPU::new();
+        cpu.load_rom(rom);
+        cpu
+    }
+
+    #[test]
+    fn illegal_opcodes_lock_the_cpu_up() {
+        for opcode in [0xD3, 0xFD] {
+            let mut cpu = cpu_with(&[0x00, opcode, 0x00]);
+            cpu.step();
+            cpu.step();
+            assert!(cpu.is_locked());
+            assert_eq!(cpu.pc, 1);
+            // still locked, the pc stays put while the rest of the system runs on
+            let cycles = cpu.bus.cycles();
+            cpu.step();
+            assert_eq!((cpu.pc, cpu.bus.cycles()), (1, cycles + 4));
+            assert_eq!(cpu.take_events(), vec![CpuEvent::Lockup { pc: 1, opcode }]);
+            assert!(cpu.take_events().is_empty());
+        }
+    }
+
+    #[test]
+    fn stop_with_key1_armed_switches_speed() {
+        let mut cpu = cpu_with(&[0x10, 0x00, 0x00]);
+        cpu.bus_mut().set_model(Model::Cgb);
+        cpu.bus_mut().write_byte(0xFF4D, 0x01);
+        cpu.step();
+        assert!(cpu.bus().double_speed());
+        assert!(!cpu.bus().speed_switch_armed());
+        assert!(!cpu.is_stopped());
+        assert_eq!(cpu.pc, 2);
+        assert_eq!(cpu.bus().read_byte(0xFF4D) & 0x81, 0x80);
+    }
+
+    #[test]
+    fn stop_waits_for_a_button() {
+        let mut cpu = cpu_with(&[0x10, 0x00, 0x00]);
+        cpu.bus_mut().write_byte(0xFF00, 0x20); // d-pad
+        cpu.bus_mut().set_button(Button::A, true); // not on the selected line
+        cpu.step();
+        assert!(cpu.is_stopped());
+        let cycles = cpu.bus().cycles();
+        cpu.step();
+        assert!(cpu.is_stopped());
+        assert_eq!(cpu.bus().cycles(), cycles);
+        cpu.bus_mut().set_button(Button::Down, true);
+        cpu.step();
+        assert!(!cpu.is_stopped());
+        assert_eq!(cpu.pc, 3);
+    }
+}
@@ -3,6 +3,7 @@ pub const SUBTRACT_FLAG_BYTE_POSITION: u8 = 6;
 pub const HALF_CARRY_FLAG_BYTE_POSITION: u8 = 5;
 pub const CARRY_FLAG_BYTE_POSITION: u8 = 4;
 
+#[allow(non_camel_case_types)]
 pub enum Flags {
     ZERO, SUBTRACT, HALF_CARRY, CARRY
 }
@@ -22,6 +23,12 @@ Flag register {f}
 └-+> Zero
   └-> Half Carry
 */
+impl Default for FlagsRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FlagsRegister {
     pub fn new() -> Self {
         FlagsRegister { 
@@ -0,0 +1,102 @@
+/* CGB VRAM DMA
+   0xFF51 HDMA1  source high
+   0xFF52 HDMA2  source low, bits 0-3 ignored
+   0xFF53 HDMA3  destination high, only bits 0-4 count, it always lands in 0x8000 - 0x9FF0
+   0xFF54 HDMA4  destination low, bits 0-3 ignored
+   0xFF55 HDMA5  bits 0-6 length in 16 byte blocks minus one, bit 7 mode
+   HDMA1-4 are write only and read 0xFF.
+
+   Writing HDMA5 with bit 7 clear runs a general purpose DMA, the whole length is copied
+   at once and the cpu is stalled until it's done. With bit 7 set it's an HBlank DMA,
+   one block goes over at the start of every HBlank (the cpu is stalled for that block).
+   Writing HDMA5 with bit 7 clear while an HBlank DMA runs stops it instead.
+
+   Reading HDMA5 gives the blocks left minus one in bits 0-6, bit 7 is clear while an
+   HBlank DMA is running and set otherwise, so 0xFF once a transfer completes.
+   The source and destination registers keep counting up as blocks are copied.
+
+   Each block takes 8 m-cycles at normal speed. The DMA runs off the same clock in double
+   speed mode, so that's 16 of the cpu's m-cycles there.
+*/
+pub const BLOCK_SIZE: u16 = 0x10;
+const BLOCK_CYCLES: u32 = 32; // t-cycles per block at normal speed
+
+pub struct Hdma {
+    source: u16,
+    destination: u16, // offset into vram
+    blocks_left: u8,
+    hblank_active: bool,
+}
+
+impl Default for Hdma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hdma {
+    pub fn new() -> Self {
+        Hdma {
+            source: 0,
+            destination: 0,
+            blocks_left: 0,
+            hblank_active: false,
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF55 => {
+                let remaining = self.blocks_left.wrapping_sub(1) & 0x7F;
+                if self.hblank_active { remaining } else { remaining | 0x80 }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    // returns how many blocks to copy right away for a general purpose DMA
+    pub fn write(&mut self, address: u16, value: u8) -> u8 {
+        match address {
+            0xFF51 => self.source = (self.source & 0x00FF) | ((value as u16) << 8),
+            0xFF52 => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+            0xFF53 => self.destination = (self.destination & 0x00FF) | (((value & 0x1F) as u16) << 8),
+            0xFF54 => self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16,
+            _ => {
+                if self.hblank_active && value & 0x80 == 0 {
+                    self.hblank_active = false;
+                    return 0;
+                }
+                self.blocks_left = (value & 0x7F) + 1;
+                if value & 0x80 != 0 {
+                    self.hblank_active = true;
+                } else {
+                    return self.blocks_left;
+                }
+            }
+        }
+        0
+    }
+
+    pub fn hblank_active(&self) -> bool {
+        self.hblank_active
+    }
+
+    // where the next block comes from and goes to, moves the registers on to the block after
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, self.destination);
+        self.source = self.source.wrapping_add(BLOCK_SIZE);
+        self.destination = (self.destination + BLOCK_SIZE) & 0x1FFF;
+        self.blocks_left = self.blocks_left.saturating_sub(1);
+        // running off the end of vram ends the transfer too
+        if self.blocks_left == 0 || self.destination == 0 {
+            self.blocks_left = 0;
+            self.hblank_active = false;
+        }
+        block
+    }
+
+    // the t-cycles the cpu is held for per block
+    pub fn block_cycles(double_speed: bool) -> u32 {
+        if double_speed { BLOCK_CYCLES * 2 } else { BLOCK_CYCLES }
+    }
+}
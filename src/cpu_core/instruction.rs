@@ -39,7 +39,8 @@ pub enum Instruction {
     HALT,
     RES(u8, PrefixTarget),
     RLCA,
-    STOP(u8)
+    STOP(u8),
+    ILLEGAL(u8), // unused opcode, hangs the cpu until power off
 }
 impl Instruction {
     pub fn from_byte(byte: u8, prefixed: bool) -> Option<Instruction> {
@@ -53,43 +54,41 @@ impl Instruction {
         match byte {
             0x00..=0x0f => Self::handle_byte_00_to_0f_prefixed(byte), 
             0x10..=0x1f => Self::handle_byte_10_to_1f_prefixed(byte),
-            0x21..=0x30 => Self::handle_byte_20_to_2f_prefixed(byte),
-            0x31..=0x40 => Self::handle_byte_30_to_3f_prefixed(byte),
-            0x41..=0x50 => Self::handle_byte_40_to_4f_prefixed(byte),
-            0x51..=0x60 => Self::handle_byte_50_to_5f_prefixed(byte),
-            0x61..=0x70 => Self::handle_byte_60_to_6f_prefixed(byte),
-            0x71..=0x80 => Self::handle_byte_70_to_7f_prefixed(byte),
-            0x81..=0x90 => Self::handle_byte_80_to_8f_prefixed(byte),
-            0x91..=0xa0 => Self::handle_byte_90_to_9f_prefixed(byte),
-            0xa1..=0xb0 => Self::handle_byte_a0_to_af_prefixed(byte),
-            0xb1..=0xc0 => Self::handle_byte_b0_to_bf_prefixed(byte),
-            0xc1..=0xd0 => Self::handle_byte_c0_to_cf_prefixed(byte),
-            0xd1..=0xe0 => Self::handle_byte_d0_to_df_prefixed(byte),
-            0xe1..=0xf0 => Self::handle_byte_e0_to_ef_prefixed(byte),
-            0xf1..=0xff => Self::handle_byte_f0_to_ff_prefixed(byte),
-            _ => panic!("no Instruction map for: prefixed 0x{:x}", byte),
+            0x20..=0x2f => Self::handle_byte_20_to_2f_prefixed(byte),
+            0x30..=0x3f => Self::handle_byte_30_to_3f_prefixed(byte),
+            0x40..=0x4f => Self::handle_byte_40_to_4f_prefixed(byte),
+            0x50..=0x5f => Self::handle_byte_50_to_5f_prefixed(byte),
+            0x60..=0x6f => Self::handle_byte_60_to_6f_prefixed(byte),
+            0x70..=0x7f => Self::handle_byte_70_to_7f_prefixed(byte),
+            0x80..=0x8f => Self::handle_byte_80_to_8f_prefixed(byte),
+            0x90..=0x9f => Self::handle_byte_90_to_9f_prefixed(byte),
+            0xa0..=0xaf => Self::handle_byte_a0_to_af_prefixed(byte),
+            0xb0..=0xbf => Self::handle_byte_b0_to_bf_prefixed(byte),
+            0xc0..=0xcf => Self::handle_byte_c0_to_cf_prefixed(byte),
+            0xd0..=0xdf => Self::handle_byte_d0_to_df_prefixed(byte),
+            0xe0..=0xef => Self::handle_byte_e0_to_ef_prefixed(byte),
+            0xf0..=0xff => Self::handle_byte_f0_to_ff_prefixed(byte),
         }
     }
 
     fn from_byte_not_prefixed(byte: u8) -> Option<Instruction> {
         match byte {
-            0x00..=0x10 => Self::handle_byte_00_to_0f(byte), 
-            0x11..=0x20 => Self::handle_byte_10_to_1f(byte),
-            0x21..=0x30 => Self::handle_byte_20_to_2f(byte),
-            0x31..=0x40 => Self::handle_byte_30_to_3f(byte),
-            0x41..=0x50 => Self::handle_byte_40_to_4f(byte),
-            0x51..=0x60 => Self::handle_byte_50_to_5f(byte),
-            0x61..=0x70 => Self::handle_byte_60_to_6f(byte),
-            0x71..=0x80 => Self::handle_byte_70_to_7f(byte),
-            0x81..=0x90 => Self::handle_byte_80_to_8f(byte),
-            0x91..=0xa0 => Self::handle_byte_90_to_9f(byte),
-            0xa1..=0xb0 => Self::handle_byte_a0_to_af(byte),
-            0xb1..=0xc0 => Self::handle_byte_b0_to_bf(byte),
-            0xc1..=0xd0 => Self::handle_byte_c0_to_cf(byte),
-            0xd1..=0xe0 => Self::handle_byte_d0_to_df(byte),
-            0xe1..=0xf0 => Self::handle_byte_e0_to_ef(byte),
-            0xf1..=0xff => Self::handle_byte_f0_to_ff(byte),
-            _ => panic!("no Instruction map for: 0x{:x}", byte) 
+            0x00..=0x0f => Self::handle_byte_00_to_0f(byte), 
+            0x10..=0x1f => Self::handle_byte_10_to_1f(byte),
+            0x20..=0x2f => Self::handle_byte_20_to_2f(byte),
+            0x30..=0x3f => Self::handle_byte_30_to_3f(byte),
+            0x40..=0x4f => Self::handle_byte_40_to_4f(byte),
+            0x50..=0x5f => Self::handle_byte_50_to_5f(byte),
+            0x60..=0x6f => Self::handle_byte_60_to_6f(byte),
+            0x70..=0x7f => Self::handle_byte_70_to_7f(byte),
+            0x80..=0x8f => Self::handle_byte_80_to_8f(byte),
+            0x90..=0x9f => Self::handle_byte_90_to_9f(byte),
+            0xa0..=0xaf => Self::handle_byte_a0_to_af(byte),
+            0xb0..=0xbf => Self::handle_byte_b0_to_bf(byte),
+            0xc0..=0xcf => Self::handle_byte_c0_to_cf(byte),
+            0xd0..=0xdf => Self::handle_byte_d0_to_df(byte),
+            0xe0..=0xef => Self::handle_byte_e0_to_ef(byte),
+            0xf0..=0xff => Self::handle_byte_f0_to_ff(byte),
         }
     }
 
@@ -111,6 +110,7 @@ impl Instruction {
             0x0D => Some(Instruction::RRC(PrefixTarget::L)),
             0x0E => Some(Instruction::RRC(PrefixTarget::HL)),
             0x0F => Some(Instruction::RRC(PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -132,6 +132,7 @@ impl Instruction {
             0x1D => Some(Instruction::RR(PrefixTarget::L)),
             0x1E => Some(Instruction::RR(PrefixTarget::HL)),
             0x1F => Some(Instruction::RR(PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -153,6 +154,7 @@ impl Instruction {
             0x2D => Some(Instruction::SRA(PrefixTarget::L)),
             0x2E => Some(Instruction::SRA(PrefixTarget::HL)),
             0x2F => Some(Instruction::SRA(PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -174,6 +176,7 @@ impl Instruction {
             0x3D => Some(Instruction::SRL(PrefixTarget::L)),
             0x3E => Some(Instruction::SRL(PrefixTarget::HL)),
             0x3F => Some(Instruction::SRL(PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -195,6 +198,7 @@ impl Instruction {
             0x4D => Some(Instruction::BIT(1, PrefixTarget::L)),
             0x4E => Some(Instruction::BIT(1, PrefixTarget::HL)),
             0x4F => Some(Instruction::BIT(1, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -216,6 +220,7 @@ impl Instruction {
             0x5D => Some(Instruction::BIT(3, PrefixTarget::L)),
             0x5E => Some(Instruction::BIT(3, PrefixTarget::HL)),
             0x5F => Some(Instruction::BIT(3, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -237,6 +242,7 @@ impl Instruction {
             0x6D => Some(Instruction::BIT(5, PrefixTarget::L)),
             0x6E => Some(Instruction::BIT(5, PrefixTarget::HL)),
             0x6F => Some(Instruction::BIT(5, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -258,6 +264,7 @@ impl Instruction {
             0x7D => Some(Instruction::BIT(7, PrefixTarget::L)),
             0x7E => Some(Instruction::BIT(7, PrefixTarget::HL)),
             0x7F => Some(Instruction::BIT(7, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -279,6 +286,7 @@ impl Instruction {
             0x8D => Some(Instruction::RES(1, PrefixTarget::L)),
             0x8E => Some(Instruction::RES(1, PrefixTarget::HL)),
             0x8F => Some(Instruction::RES(1, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -300,6 +308,7 @@ impl Instruction {
             0x9D => Some(Instruction::RES(3, PrefixTarget::L)),
             0x9E => Some(Instruction::RES(3, PrefixTarget::HL)),
             0x9F => Some(Instruction::RES(3, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -321,6 +330,7 @@ impl Instruction {
             0xAD => Some(Instruction::RES(5, PrefixTarget::L)),
             0xAE => Some(Instruction::RES(5, PrefixTarget::HL)),
             0xAF => Some(Instruction::RES(5, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -342,6 +352,7 @@ impl Instruction {
             0xBD => Some(Instruction::RES(7, PrefixTarget::L)),
             0xBE => Some(Instruction::RES(7, PrefixTarget::HL)),
             0xBF => Some(Instruction::RES(7, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -363,6 +374,7 @@ impl Instruction {
             0xCD => Some(Instruction::SET(1, PrefixTarget::L)),
             0xCE => Some(Instruction::SET(1, PrefixTarget::HL)),
             0xCF => Some(Instruction::SET(1, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -384,6 +396,7 @@ impl Instruction {
             0xDD => Some(Instruction::SET(3, PrefixTarget::L)),
             0xDE => Some(Instruction::SET(3, PrefixTarget::HL)),
             0xDF => Some(Instruction::SET(3, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -405,6 +418,7 @@ impl Instruction {
             0xED => Some(Instruction::SET(5, PrefixTarget::L)),
             0xEE => Some(Instruction::SET(5, PrefixTarget::HL)),
             0xEF => Some(Instruction::SET(5, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
     
@@ -426,6 +440,7 @@ impl Instruction {
             0xFD => Some(Instruction::SET(7, PrefixTarget::L)),
             0xFE => Some(Instruction::SET(7, PrefixTarget::HL)),
             0xFF => Some(Instruction::SET(7, PrefixTarget::A)),
+            _ => unreachable!(),
         }
     }
 
@@ -447,328 +462,344 @@ impl Instruction {
             0x0D => Some(Instruction::DEC(IncDecTarget::C)),
             0x0E => Some(Instruction::LD(LoadType::Byte(LoadTarget::C, LoadSource::D8))),
             0x0F => Some(Instruction::RRCA),
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_10_to_1f(byte: u8) -> Option<Instruction> {
         match byte {
             0x10 => Some(Instruction::STOP(0)),
-            0x11 => Some(Instruction::),
-            0x12 => Some(Instruction::),
+            0x11 => None,
+            0x12 => None,
             0x13 => Some(Instruction::INC(IncDecTarget::DE)),
-            0x14 => Some(Instruction::),
-            0x15 => Some(Instruction::),
-            0x16 => Some(Instruction::),
-            0x17 => Some(Instruction::),
-            0x18 => Some(Instruction::),
-            0x19 => Some(Instruction::),
-            0x1A => Some(Instruction::),
-            0x1B => Some(Instruction::),
-            0x1C => Some(Instruction::),
-            0x1D => Some(Instruction::),
-            0x1E => Some(Instruction::),
-            0x1F => Some(Instruction::),
+            0x14 => None,
+            0x15 => None,
+            0x16 => None,
+            0x17 => None,
+            0x18 => None,
+            0x19 => None,
+            0x1A => None,
+            0x1B => None,
+            0x1C => None,
+            0x1D => None,
+            0x1E => None,
+            0x1F => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_20_to_2f(byte: u8) -> Option<Instruction> {
         match byte {
-            0x20 => Some(Instruction::),
-            0x21 => Some(Instruction::),
-            0x22 => Some(Instruction::),
-            0x23 => Some(Instruction::),
-            0x24 => Some(Instruction::),
-            0x25 => Some(Instruction::),
-            0x26 => Some(Instruction::),
-            0x27 => Some(Instruction::),
-            0x28 => Some(Instruction::),
-            0x29 => Some(Instruction::),
-            0x2A => Some(Instruction::),
-            0x2B => Some(Instruction::),
-            0x2C => Some(Instruction::),
-            0x2D => Some(Instruction::),
-            0x2E => Some(Instruction::),
-            0x2F => Some(Instruction::),
+            0x20 => None,
+            0x21 => None,
+            0x22 => None,
+            0x23 => None,
+            0x24 => None,
+            0x25 => None,
+            0x26 => None,
+            0x27 => None,
+            0x28 => None,
+            0x29 => None,
+            0x2A => None,
+            0x2B => None,
+            0x2C => None,
+            0x2D => None,
+            0x2E => None,
+            0x2F => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_30_to_3f(byte: u8) -> Option<Instruction> {
         match byte {
-            0x30 => Some(Instruction::),
-            0x31 => Some(Instruction::),
-            0x32 => Some(Instruction::),
-            0x33 => Some(Instruction::),
-            0x34 => Some(Instruction::),
-            0x35 => Some(Instruction::),
-            0x36 => Some(Instruction::),
-            0x37 => Some(Instruction::),
-            0x38 => Some(Instruction::),
-            0x39 => Some(Instruction::),
-            0x3A => Some(Instruction::),
-            0x3B => Some(Instruction::),
+            0x30 => None,
+            0x31 => None,
+            0x32 => None,
+            0x33 => None,
+            0x34 => None,
+            0x35 => None,
+            0x36 => None,
+            0x37 => None,
+            0x38 => None,
+            0x39 => None,
+            0x3A => None,
+            0x3B => None,
             0x3C => Some(Instruction::INC(IncDecTarget::A)),
-            0x3D => Some(Instruction::),
-            0x3E => Some(Instruction::),
-            0x3F => Some(Instruction::),
+            0x3D => None,
+            0x3E => None,
+            0x3F => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_40_to_4f(byte: u8) -> Option<Instruction> {
         match byte {
-            0x40 => Some(Instruction::),
-            0x41 => Some(Instruction::),
-            0x42 => Some(Instruction::),
-            0x43 => Some(Instruction::),
-            0x44 => Some(Instruction::),
-            0x45 => Some(Instruction::),
-            0x46 => Some(Instruction::),
-            0x47 => Some(Instruction::),
-            0x48 => Some(Instruction::),
-            0x49 => Some(Instruction::),
-            0x4A => Some(Instruction::),
-            0x4B => Some(Instruction::),
-            0x4C => Some(Instruction::),
-            0x4D => Some(Instruction::),
-            0x4E => Some(Instruction::),
-            0x4F => Some(Instruction::),
+            0x40 => None,
+            0x41 => None,
+            0x42 => None,
+            0x43 => None,
+            0x44 => None,
+            0x45 => None,
+            0x46 => None,
+            0x47 => None,
+            0x48 => None,
+            0x49 => None,
+            0x4A => None,
+            0x4B => None,
+            0x4C => None,
+            0x4D => None,
+            0x4E => None,
+            0x4F => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_50_to_5f(byte: u8) -> Option<Instruction> {
         match byte {
-            0x50 => Some(Instruction::),
-            0x51 => Some(Instruction::),
-            0x52 => Some(Instruction::),
-            0x53 => Some(Instruction::),
-            0x54 => Some(Instruction::),
-            0x55 => Some(Instruction::),
-            0x56 => Some(Instruction::),
-            0x57 => Some(Instruction::),
-            0x58 => Some(Instruction::),
-            0x59 => Some(Instruction::),
-            0x5A => Some(Instruction::),
-            0x5B => Some(Instruction::),
-            0x5C => Some(Instruction::),
-            0x5D => Some(Instruction::),
-            0x5E => Some(Instruction::),
-            0x5F => Some(Instruction::),
+            0x50 => None,
+            0x51 => None,
+            0x52 => None,
+            0x53 => None,
+            0x54 => None,
+            0x55 => None,
+            0x56 => None,
+            0x57 => None,
+            0x58 => None,
+            0x59 => None,
+            0x5A => None,
+            0x5B => None,
+            0x5C => None,
+            0x5D => None,
+            0x5E => None,
+            0x5F => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_60_to_6f(byte: u8) -> Option<Instruction> {
         match byte {
-            0x60 => Some(Instruction::),
-            0x61 => Some(Instruction::),
-            0x62 => Some(Instruction::),
-            0x63 => Some(Instruction::),
-            0x64 => Some(Instruction::),
-            0x65 => Some(Instruction::),
-            0x66 => Some(Instruction::),
-            0x67 => Some(Instruction::),
-            0x68 => Some(Instruction::),
-            0x69 => Some(Instruction::),
-            0x6A => Some(Instruction::),
-            0x6B => Some(Instruction::),
-            0x6C => Some(Instruction::),
-            0x6D => Some(Instruction::),
-            0x6E => Some(Instruction::),
-            0x6F => Some(Instruction::),
+            0x60 => None,
+            0x61 => None,
+            0x62 => None,
+            0x63 => None,
+            0x64 => None,
+            0x65 => None,
+            0x66 => None,
+            0x67 => None,
+            0x68 => None,
+            0x69 => None,
+            0x6A => None,
+            0x6B => None,
+            0x6C => None,
+            0x6D => None,
+            0x6E => None,
+            0x6F => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_70_to_7f(byte: u8) -> Option<Instruction> {
         match byte {
-            0x70 => Some(Instruction::),
-            0x71 => Some(Instruction::),
-            0x72 => Some(Instruction::),
-            0x73 => Some(Instruction::),
-            0x74 => Some(Instruction::),
-            0x75 => Some(Instruction::),
-            0x76 => Some(Instruction::),
-            0x77 => Some(Instruction::),
-            0x78 => Some(Instruction::),
-            0x79 => Some(Instruction::),
-            0x7A => Some(Instruction::),
-            0x7B => Some(Instruction::),
-            0x7C => Some(Instruction::),
-            0x7D => Some(Instruction::),
-            0x7E => Some(Instruction::),
+            0x70 => None,
+            0x71 => None,
+            0x72 => None,
+            0x73 => None,
+            0x74 => None,
+            0x75 => None,
+            0x76 => None,
+            0x77 => None,
+            0x78 => None,
+            0x79 => None,
+            0x7A => None,
+            0x7B => None,
+            0x7C => None,
+            0x7D => None,
+            0x7E => None,
             0x7F => Some(Instruction::LD(LoadType::Byte(LoadTarget::A, LoadSource::A))),
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_80_to_8f(byte: u8) -> Option<Instruction> {
         match byte {
-            0x80 => Some(Instruction::),
-            0x81 => Some(Instruction::),
-            0x82 => Some(Instruction::),
-            0x83 => Some(Instruction::),
-            0x84 => Some(Instruction::),
-            0x85 => Some(Instruction::),
-            0x86 => Some(Instruction::),
-            0x87 => Some(Instruction::),
-            0x88 => Some(Instruction::),
-            0x89 => Some(Instruction::),
-            0x8A => Some(Instruction::),
-            0x8B => Some(Instruction::),
-            0x8C => Some(Instruction::),
-            0x8D => Some(Instruction::),
-            0x8E => Some(Instruction::),
-            0x8F => Some(Instruction::),
+            0x80 => None,
+            0x81 => None,
+            0x82 => None,
+            0x83 => None,
+            0x84 => None,
+            0x85 => None,
+            0x86 => None,
+            0x87 => None,
+            0x88 => None,
+            0x89 => None,
+            0x8A => None,
+            0x8B => None,
+            0x8C => None,
+            0x8D => None,
+            0x8E => None,
+            0x8F => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_90_to_9f(byte: u8) -> Option<Instruction> {
         match byte {
-            0x90 => Some(Instruction::),
-            0x91 => Some(Instruction::),
-            0x92 => Some(Instruction::),
-            0x93 => Some(Instruction::),
-            0x94 => Some(Instruction::),
-            0x95 => Some(Instruction::),
-            0x96 => Some(Instruction::),
-            0x97 => Some(Instruction::),
-            0x98 => Some(Instruction::),
-            0x99 => Some(Instruction::),
-            0x9A => Some(Instruction::),
-            0x9B => Some(Instruction::),
-            0x9C => Some(Instruction::),
-            0x9D => Some(Instruction::),
-            0x9E => Some(Instruction::),
-            0x9F => Some(Instruction::),
+            0x90 => None,
+            0x91 => None,
+            0x92 => None,
+            0x93 => None,
+            0x94 => None,
+            0x95 => None,
+            0x96 => None,
+            0x97 => None,
+            0x98 => None,
+            0x99 => None,
+            0x9A => None,
+            0x9B => None,
+            0x9C => None,
+            0x9D => None,
+            0x9E => None,
+            0x9F => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_a0_to_af(byte: u8) -> Option<Instruction> {
         match byte {
-            0xA0 => Some(Instruction::),
-            0xA1 => Some(Instruction::),
-            0xA2 => Some(Instruction::),
-            0xA3 => Some(Instruction::),
-            0xA4 => Some(Instruction::),
-            0xA5 => Some(Instruction::),
-            0xA6 => Some(Instruction::),
-            0xA7 => Some(Instruction::),
-            0xA8 => Some(Instruction::),
-            0xA9 => Some(Instruction::),
-            0xAA => Some(Instruction::),
-            0xAB => Some(Instruction::),
-            0xAC => Some(Instruction::),
-            0xAD => Some(Instruction::),
-            0xAE => Some(Instruction::),
-            0xAF => Some(Instruction::),
+            0xA0 => None,
+            0xA1 => None,
+            0xA2 => None,
+            0xA3 => None,
+            0xA4 => None,
+            0xA5 => None,
+            0xA6 => None,
+            0xA7 => None,
+            0xA8 => None,
+            0xA9 => None,
+            0xAA => None,
+            0xAB => None,
+            0xAC => None,
+            0xAD => None,
+            0xAE => None,
+            0xAF => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_b0_to_bf(byte: u8) -> Option<Instruction> {
         match byte {
-            0xB0 => Some(Instruction::),
-            0xB1 => Some(Instruction::),
-            0xB2 => Some(Instruction::),
-            0xB3 => Some(Instruction::),
-            0xB4 => Some(Instruction::),
-            0xB5 => Some(Instruction::),
-            0xB6 => Some(Instruction::),
-            0xB7 => Some(Instruction::),
-            0xB8 => Some(Instruction::),
-            0xB9 => Some(Instruction::),
-            0xBA => Some(Instruction::),
-            0xBB => Some(Instruction::),
-            0xBC => Some(Instruction::),
-            0xBD => Some(Instruction::),
-            0xBE => Some(Instruction::),
-            0xBF => Some(Instruction::),
+            0xB0 => None,
+            0xB1 => None,
+            0xB2 => None,
+            0xB3 => None,
+            0xB4 => None,
+            0xB5 => None,
+            0xB6 => None,
+            0xB7 => None,
+            0xB8 => None,
+            0xB9 => None,
+            0xBA => None,
+            0xBB => None,
+            0xBC => None,
+            0xBD => None,
+            0xBE => None,
+            0xBF => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_c0_to_cf(byte: u8) -> Option<Instruction> {
         match byte {
-            0xC0 => Some(Instruction::),
-            0xC1 => Some(Instruction::),
-            0xC2 => Some(Instruction::),
-            0xC3 => Some(Instruction::),
-            0xC4 => Some(Instruction::),
-            0xC5 => Some(Instruction::),
-            0xC6 => Some(Instruction::),
-            0xC7 => Some(Instruction::),
-            0xC8 => Some(Instruction::),
-            0xC9 => Some(Instruction::),
-            0xCA => Some(Instruction::),
-            0xCB => Some(Instruction::),
-            0xCC => Some(Instruction::),
-            0xCD => Some(Instruction::),
-            0xCE => Some(Instruction::),
-            0xCF => Some(Instruction::),
+            0xC0 => None,
+            0xC1 => None,
+            0xC2 => None,
+            0xC3 => None,
+            0xC4 => None,
+            0xC5 => None,
+            0xC6 => None,
+            0xC7 => None,
+            0xC8 => None,
+            0xC9 => None,
+            0xCA => None,
+            0xCB => None,
+            0xCC => None,
+            0xCD => None,
+            0xCE => None,
+            0xCF => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_d0_to_df(byte: u8) -> Option<Instruction> {
         match byte {
-            0xD0 => Some(Instruction::),
-            0xD1 => Some(Instruction::),
-            0xD2 => Some(Instruction::),
-            0xD3 => Some(Instruction::),
-            0xD4 => Some(Instruction::),
-            0xD5 => Some(Instruction::),
-            0xD6 => Some(Instruction::),
-            0xD7 => Some(Instruction::),
-            0xD8 => Some(Instruction::),
-            0xD9 => Some(Instruction::),
-            0xDA => Some(Instruction::),
-            0xDB => Some(Instruction::),
-            0xDC => Some(Instruction::),
-            0xDD => Some(Instruction::),
-            0xDE => Some(Instruction::),
-            0xDF => Some(Instruction::),
+            0xD0 => None,
+            0xD1 => None,
+            0xD2 => None,
+            0xD3 => Some(Instruction::ILLEGAL(0xD3)),
+            0xD4 => None,
+            0xD5 => None,
+            0xD6 => None,
+            0xD7 => None,
+            0xD8 => None,
+            0xD9 => None,
+            0xDA => None,
+            0xDB => Some(Instruction::ILLEGAL(0xDB)),
+            0xDC => None,
+            0xDD => Some(Instruction::ILLEGAL(0xDD)),
+            0xDE => None,
+            0xDF => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_e0_to_ef(byte: u8) -> Option<Instruction> {
         match byte {
-            0xE0 => Some(Instruction::),
-            0xE1 => Some(Instruction::),
-            0xE2 => Some(Instruction::),
-            0xE3 => Some(Instruction::),
-            0xE4 => Some(Instruction::),
-            0xE5 => Some(Instruction::),
-            0xE6 => Some(Instruction::),
-            0xE7 => Some(Instruction::),
-            0xE8 => Some(Instruction::),
-            0xE9 => Some(Instruction::),
-            0xEA => Some(Instruction::),
-            0xEB => Some(Instruction::),
-            0xEC => Some(Instruction::),
-            0xED => Some(Instruction::),
-            0xEE => Some(Instruction::),
-            0xEF => Some(Instruction::),
+            0xE0 => None,
+            0xE1 => None,
+            0xE2 => None,
+            0xE3 => Some(Instruction::ILLEGAL(0xE3)),
+            0xE4 => Some(Instruction::ILLEGAL(0xE4)),
+            0xE5 => None,
+            0xE6 => None,
+            0xE7 => None,
+            0xE8 => None,
+            0xE9 => None,
+            0xEA => None,
+            0xEB => Some(Instruction::ILLEGAL(0xEB)),
+            0xEC => Some(Instruction::ILLEGAL(0xEC)),
+            0xED => Some(Instruction::ILLEGAL(0xED)),
+            0xEE => None,
+            0xEF => None,
+            _ => unreachable!(),
         }
     }
     
     fn handle_byte_f0_to_ff(byte: u8) -> Option<Instruction> {
         match byte {
-            0xF0 => Some(Instruction::),
-            0xF1 => Some(Instruction::),
-            0xF2 => Some(Instruction::),
-            0xF3 => Some(Instruction::),
-            0xF4 => Some(Instruction::),
-            0xF5 => Some(Instruction::),
-            0xF6 => Some(Instruction::),
-            0xF7 => Some(Instruction::),
-            0xF8 => Some(Instruction::),
-            0xF9 => Some(Instruction::),
-            0xFA => Some(Instruction::),
-            0xFB => Some(Instruction::),
-            0xFC => Some(Instruction::),
-            0xFD => Some(Instruction::),
-            0xFE => Some(Instruction::),
-            0xFF => Some(Instruction::),
+            0xF0 => None,
+            0xF1 => None,
+            0xF2 => None,
+            0xF3 => None,
+            0xF4 => Some(Instruction::ILLEGAL(0xF4)),
+            0xF5 => None,
+            0xF6 => None,
+            0xF7 => None,
+            0xF8 => None,
+            0xF9 => None,
+            0xFA => None,
+            0xFB => None,
+            0xFC => Some(Instruction::ILLEGAL(0xFC)),
+            0xFD => Some(Instruction::ILLEGAL(0xFD)),
+            0xFE => None,
+            0xFF => None,
+            _ => unreachable!(),
         }
     }
 
 }
 
 #[derive(Debug)]
-enum PrefixTarget {
+pub enum PrefixTarget {
     A, B, C, D, E, H, L, HL
 }
 
@@ -0,0 +1,8 @@
+// interrupt request (IF, 0xFF0F) and enable (IE, 0xFFFF) bits, lower bits win when several are pending
+pub const IF: u16 = 0xFF0F;
+
+pub const VBLANK: u8 = 0x01;
+pub const STAT: u8 = 0x02;
+pub const TIMER: u8 = 0x04;
+pub const SERIAL: u8 = 0x08;
+pub const JOYPAD: u8 = 0x10;
@@ -0,0 +1,51 @@
+/* I/O register page 0xFF00 - 0xFF7F
+   Most registers don't implement all 8 bits, the missing bits read back as 1.
+   Addresses with nothing behind them read as 0xFF and ignore writes.
+   Peripherals that own a register take it over from here as they get implemented.
+*/
+
+// bits that always read as 1, indexed by address - 0xFF00
+const UNUSED_BITS: [u8; 0x80] = [
+    // 0xFF00 P1, SB, SC, -, DIV, TIMA, TMA, TAC, -, -, -, -, -, -, -, IF
+    0xCF, 0x00, 0x7E, 0xFF, 0x00, 0x00, 0x00, 0xF8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xE0,
+    // 0xFF10 NR10, NR11, NR12, NR13, NR14, -, NR21, NR22, NR23, NR24, NR30, NR31, NR32, NR33, NR34, -
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF, 0x7F, 0xFF, 0x9F, 0xFF, 0xBF, 0xFF,
+    // 0xFF20 NR41, NR42, NR43, NR44, NR50, NR51, NR52, -, ...
+    0xFF, 0x00, 0x00, 0xBF, 0x00, 0x00, 0x70, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    // 0xFF30 wave ram
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // 0xFF40 LCDC, STAT, SCY, SCX, LY, LYC, DMA, BGP, OBP0, OBP1, WY, WX, -, -, -, -
+    0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF,
+    // 0xFF50 - 0xFF7F nothing on DMG
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+pub struct IoRegisters {
+    registers: [u8; 0x80],
+}
+
+impl Default for IoRegisters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoRegisters {
+    pub fn new() -> Self {
+        IoRegisters {
+            registers: [0; 0x80],
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        let index = (address - 0xFF00) as usize;
+        self.registers[index] | UNUSED_BITS[index]
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        let index = (address - 0xFF00) as usize;
+        self.registers[index] = value & !UNUSED_BITS[index];
+    }
+}
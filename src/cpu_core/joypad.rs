@@ -0,0 +1,88 @@
+/* Joypad, P1 (0xFF00)
+   The eight buttons sit on two lines of four. The game clears bit 4 to read the d-pad and
+   bit 5 to read the rest, bits 0-3 then read 0 for every button held on a selected line.
+     bit 0  right / A   bit 1  left / B   bit 2  up / select   bit 3  down / start
+   Bits 0-3 are read only, bits 6-7 always read 1.
+
+   An input going from 1 to 0 requests the joypad interrupt and wakes the cpu from STOP.
+   That happens when a button is pressed on a selected line or when a line with a button
+   already held gets selected.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    // bit in held, the d-pad in 0-3 and the others in 4-7, in P1 order
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+const SELECT_DPAD: u8 = 0x10;
+const SELECT_BUTTONS: u8 = 0x20;
+
+pub struct Joypad {
+    select: u8, // bits 4-5 as written
+    held: u8,
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad { select: 0, held: 0 }
+    }
+
+    pub fn read(&self) -> u8 {
+        0xC0 | self.select | self.inputs()
+    }
+
+    // returns true if an input went low
+    pub fn write(&mut self, value: u8) -> bool {
+        let before = self.inputs();
+        self.select = value & (SELECT_DPAD | SELECT_BUTTONS);
+        before & !self.inputs() != 0
+    }
+
+    // the host reports a button going down or up, returns true if an input went low
+    pub fn set(&mut self, button: Button, held: bool) -> bool {
+        let before = self.inputs();
+        if held {
+            self.held |= button.mask();
+        } else {
+            self.held &= !button.mask();
+        }
+        before & !self.inputs() != 0
+    }
+
+    // a held button shows up on a selected line
+    pub fn pressed(&self) -> bool {
+        self.inputs() != 0x0F
+    }
+
+    // bits 0-3 of P1, low for held buttons on selected lines
+    fn inputs(&self) -> u8 {
+        let mut low = 0;
+        if self.select & SELECT_DPAD == 0 {
+            low |= self.held & 0x0F;
+        }
+        if self.select & SELECT_BUTTONS == 0 {
+            low |= self.held >> 4;
+        }
+        !low & 0x0F
+    }
+}
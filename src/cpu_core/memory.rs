@@ -1,17 +1,639 @@
+use crate::cartridge::mapper::{self, Cartridge};
+use crate::cartridge::rom_only::RomOnly;
+use crate::cpu_core::hdma::{Hdma, BLOCK_SIZE};
+use crate::cpu_core::interrupts::{self, IF};
+use crate::cpu_core::io_registers::IoRegisters;
+use crate::cpu_core::joypad::{Button, Joypad};
+use crate::cpu_core::model::Model;
+use crate::cpu_core::oam_bug::{self, OamBugKind};
+use crate::cpu_core::oam_dma::OamDma;
+use crate::cpu_core::observer::{AccessKind, Callback, MemoryAccess, ObserverId, Observers};
+use crate::ppu_core::ppu::Ppu;
+use std::cell::Cell;
+use std::ops::RangeInclusive;
+
+/* Memory map
+   0x0000 - 0x7FFF  cartridge rom (bank 0 then the switchable bank)
+   0x8000 - 0x9FFF  video ram, CGB has two banks picked by VBK (0xFF4F)
+   0xA000 - 0xBFFF  cartridge ram
+   0xC000 - 0xCFFF  work ram bank 0
+   0xD000 - 0xDFFF  work ram bank 1, CGB can switch in banks 1-7 with SVBK (0xFF70)
+   0xE000 - 0xFDFF  echo ram, mirror of 0xC000 - 0xDDFF
+   0xFE00 - 0xFE9F  object attribute memory (OAM)
+   0xFEA0 - 0xFEFF  unusable
+   0xFF00 - 0xFF7F  I/O registers, P1 belongs to the joypad, 0xFF40 - 0xFF4B to the ppu
+                    (except DMA), so do the CGB palette registers 0xFF68 - 0xFF6C
+   0xFF80 - 0xFFFE  high ram
+   0xFFFF           interrupt enable
+   While OAM DMA runs the cpu only gets through to 0xFF00 - 0xFFFF, see oam_dma.rs. That's
+   more than high ram on purpose: the I/O registers and IE sit on the cpu's own bus along with
+   high ram, and a restart has to be able to reach 0xFF46.
+   The ppu also locks the cpu out of OAM in modes 2 and 3 and out of vram in mode 3.
+*/
+pub const VRAM_SIZE: usize = 0x2000;
+pub const VRAM_BANKS: usize = 2;
+pub const WRAM_BANK_SIZE: usize = 0x1000;
+pub const WRAM_BANKS: usize = 8;
+pub const OAM_SIZE: usize = 0xA0;
+pub const HRAM_SIZE: usize = 0x7F;
+
+const P1: u16 = 0xFF00;
+const DMA: u16 = 0xFF46;
+const KEY1: u16 = 0xFF4D;
+const VBK: u16 = 0xFF4F;
+const SVBK: u16 = 0xFF70;
+
 pub struct MemoryBus {
-    memory: [u8; 0x10000]
+    cartridge: Box<dyn Cartridge>,
+    vram: [u8; VRAM_SIZE * VRAM_BANKS],
+    wram: [u8; WRAM_BANK_SIZE * WRAM_BANKS],
+    vram_bank: u8, // VBK bit 0
+    wram_bank: u8, // SVBK bits 0-2, as written
+    oam: [u8; OAM_SIZE],
+    io: IoRegisters,
+    joypad: Joypad,
+    hram: [u8; HRAM_SIZE],
+    interrupt_enable: u8,
+    cycles: u64, // t-cycles elapsed since power on
+    access_cycles: Cell<u64>, // t-cycles of the current instruction the cpu has used so far, for observers
+    model: Model,
+    oam_scan_row: Option<usize>, // the OAM row the ppu is reading, only set during mode 2
+    dma: OamDma,
+    dma_cycles: u32, // t-cycles towards the next dma m-cycle
+    hdma: Hdma,
+    stall_cycles: u32, // t-cycles the cpu has to sit out, owed to a VRAM DMA
+    double_speed: bool,
+    speed_switch_armed: bool, // KEY1 bit 0, the next STOP switches speed
+    half_cycle: u32, // left over when halving cycles for normal speed parts in double speed
+    observers: Observers,
+    ppu: Ppu,
 }
+impl Default for MemoryBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MemoryBus {
     pub fn new() -> Self {
         MemoryBus { 
-            memory:[0; 0x10000] 
+            cartridge: Box::new(RomOnly::new(Vec::new(), 0)),
+            vram: [0; VRAM_SIZE * VRAM_BANKS],
+            wram: [0; WRAM_BANK_SIZE * WRAM_BANKS],
+            vram_bank: 0,
+            wram_bank: 0,
+            oam: [0; OAM_SIZE],
+            io: IoRegisters::new(),
+            joypad: Joypad::new(),
+            hram: [0; HRAM_SIZE],
+            interrupt_enable: 0,
+            cycles: 0,
+            access_cycles: Cell::new(0),
+            model: Model::Dmg,
+            oam_scan_row: None,
+            dma: OamDma::new(),
+            dma_cycles: 0,
+            hdma: Hdma::new(),
+            stall_cycles: 0,
+            double_speed: false,
+            speed_switch_armed: false,
+            half_cycle: 0,
+            observers: Observers::new(),
+            ppu: Ppu::new(),
+        }
+    }
+
+    pub fn load_rom(&mut self, data: Vec<u8>) {
+        self.cartridge = mapper::load(data);
+    }
+
+    pub fn load_cartridge(&mut self, cartridge: Box<dyn Cartridge>) {
+        self.cartridge = cartridge;
+    }
+
+    pub fn cartridge(&self) -> &dyn Cartridge {
+        self.cartridge.as_ref()
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut dyn Cartridge {
+        self.cartridge.as_mut()
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+        self.ppu.set_cgb(model.is_cgb());
+    }
+
+    // the ppu reports which OAM row it is scanning, None outside of mode 2
+    pub fn set_oam_scan_row(&mut self, row: Option<usize>) {
+        self.oam_scan_row = row
+    }
+
+    // called whenever the cpu puts an address on the bus through the 16 bit inc/dec unit or a stack access
+    pub fn trigger_oam_bug(&mut self, address: u16, kind: OamBugKind) {
+        if !self.model.is_dmg() || !(0xFE00..=0xFEFF).contains(&address) {
+            return;
+        }
+        if let Some(row) = self.oam_scan_row {
+            oam_bug::corrupt(&mut self.oam, row, kind);
+        }
+    }
+
+    // advance everything on the bus that runs independently of the cpu
+    pub fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+        self.access_cycles.set(0);
+        // the cartridge clock doesn't speed up with the cpu
+        let normal_speed_cycles = if self.double_speed {
+            let total = cycles + self.half_cycle;
+            self.half_cycle = total & 1;
+            total / 2
+        } else {
+            cycles
+        };
+        self.cartridge.tick(normal_speed_cycles);
+        self.tick_ppu(normal_speed_cycles);
+        self.dma_cycles += cycles;
+        while self.dma_cycles >= 4 {
+            self.dma_cycles -= 4;
+            self.step_dma();
+        }
+    }
+
+    fn tick_ppu(&mut self, cycles: u32) {
+        let events = self.ppu.tick(cycles, &self.vram, &self.oam);
+        if events.vblank_interrupt {
+            self.request_interrupt(interrupts::VBLANK);
+        }
+        if events.stat_interrupt {
+            self.request_interrupt(interrupts::STAT);
+        }
+        if events.hblank {
+            self.hblank();
+        }
+        self.oam_scan_row = self.ppu.oam_scan_row();
+    }
+
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    pub fn request_interrupt(&mut self, interrupt: u8) {
+        let flags = self.io.read(IF);
+        self.io.write(IF, flags | interrupt);
+    }
+
+    pub fn dma_active(&self) -> bool {
+        self.dma.active()
+    }
+
+    fn step_dma(&mut self) {
+        if let Some((source, index)) = self.dma.step() {
+            let value = match source {
+                0xE000..=0xFFFF => self.wram[self.wram_index(source - 0x2000)],
+                _ => self.read_direct(source),
+            };
+            self.oam[index] = value;
+            self.dma.set_value(value);
+        }
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    pub fn speed_switch_armed(&self) -> bool {
+        self.speed_switch_armed
+    }
+
+    // done by STOP while KEY1 is armed
+    pub fn switch_speed(&mut self) {
+        self.double_speed = !self.double_speed;
+        self.speed_switch_armed = false;
+    }
+
+    // the host's input, see joypad.rs
+    pub fn set_button(&mut self, button: Button, held: bool) {
+        if self.joypad.set(button, held) {
+            self.request_interrupt(interrupts::JOYPAD);
+        }
+    }
+
+    // a held button shows up in P1, wakes STOP
+    pub fn joypad_pressed(&self) -> bool {
+        self.joypad.pressed()
+    }
+
+    // the cpu calls this before each instruction and skips that many t-cycles
+    pub fn take_stall_cycles(&mut self) -> u32 {
+        std::mem::take(&mut self.stall_cycles)
+    }
+
+    // the ppu calls this as it enters HBlank
+    pub fn hblank(&mut self) {
+        if self.hdma.hblank_active() {
+            self.copy_hdma_block();
+        }
+    }
+
+    fn copy_hdma_block(&mut self) {
+        let (source, destination) = self.hdma.next_block();
+        for i in 0..BLOCK_SIZE {
+            let address = source.wrapping_add(i);
+            // the DMA can't read vram or anything past work ram
+            let value = match address {
+                0x8000..=0x9FFF | 0xE000..=0xFFFF => 0xFF,
+                _ => self.read_direct(address),
+            };
+            let index = self.vram_index(destination + i);
+            self.vram[index] = value;
+        }
+        self.stall_cycles += Hdma::block_cycles(self.double_speed);
+    }
+
+    // one bank of video ram, for the ppu
+    pub fn vram(&self, bank: usize) -> &[u8] {
+        &self.vram[bank * VRAM_SIZE..(bank + 1) * VRAM_SIZE]
+    }
+
+    // banking only exists on CGB, the DMG always sees bank 0 of vram and banks 0 and 1 of wram
+    fn vram_index(&self, offset: u16) -> usize {
+        let bank = if self.model.is_cgb() { self.vram_bank as usize } else { 0 };
+        bank * VRAM_SIZE + offset as usize
+    }
+
+    // address is in 0xC000 - 0xDFFF
+    fn wram_index(&self, address: u16) -> usize {
+        if address < 0xD000 {
+            return (address - 0xC000) as usize;
+        }
+        // SVBK 0 selects bank 1, there's no way to put bank 0 at 0xD000
+        let bank = if self.model.is_cgb() { self.wram_bank.max(1) as usize } else { 1 };
+        bank * WRAM_BANK_SIZE + (address - 0xD000) as usize
+    }
+
+    fn ppu_blocks(&self, address: u16) -> bool {
+        match address {
+            0x8000..=0x9FFF => self.ppu.vram_blocked(),
+            // the unusable area behind OAM goes along with it
+            0xFE00..=0xFEFF => self.ppu.oam_blocked(),
+            _ => false,
         }
     }
+
+    // what the cpu can't reach while OAM DMA is running
+    fn dma_blocks(&self, address: u16) -> bool {
+        self.dma.active() && address < 0xFF00
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
     
+    // called for every cpu access to an address in range, see observer.rs
+    pub fn add_observer(&mut self, range: RangeInclusive<u16>, callback: Callback) -> ObserverId {
+        self.observers.add(range, callback)
+    }
+
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        self.observers.remove(id)
+    }
+
+    /* Every cpu access takes an m-cycle. The bus only catches up once the instruction is done,
+       so each access is stamped with the cycle count at the start of the instruction plus the
+       m-cycles it already used. */
+    fn observe(&self, address: u16, value: u8, kind: AccessKind) -> u8 {
+        let cycle = self.cycles + self.access_cycles.get();
+        self.internal_cycle();
+        if self.observers.is_empty() {
+            return value;
+        }
+        self.observers.notify(&MemoryAccess { address, value, cycle, kind })
+    }
+
+    // an m-cycle of the current instruction that doesn't touch memory, so the accesses after it are stamped later
+    pub fn internal_cycle(&self) {
+        self.access_cycles.set(self.access_cycles.get() + 4);
+    }
+
     pub fn read_byte(&self, address: u16) -> u8 {
-        self.memory[address as usize]
+        self.observe(address, self.read_cpu(address), AccessKind::Read)
+    }
+
+    // same as read_byte, but tells observers the cpu is reading instruction bytes
+    pub fn fetch_byte(&self, address: u16) -> u8 {
+        self.observe(address, self.read_cpu(address), AccessKind::Fetch)
+    }
+
+    fn read_cpu(&self, address: u16) -> u8 {
+        if self.dma_blocks(address) {
+            return if (0xFE00..=0xFEFF).contains(&address) { 0xFF } else { self.dma.value() };
+        }
+        if self.ppu_blocks(address) {
+            return 0xFF;
+        }
+        self.read_direct(address)
+    }
+
+    // the bus without anything standing in the cpu's way
+    fn read_direct(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x7FFF => self.cartridge.read_rom(address),
+            0x8000..=0x9FFF => self.vram[self.vram_index(address - 0x8000)],
+            0xA000..=0xBFFF => self.cartridge.read_ram(address),
+            0xC000..=0xDFFF => self.wram[self.wram_index(address)],
+            0xE000..=0xFDFF => self.wram[self.wram_index(address - 0x2000)],
+            0xFE00..=0xFE9F => self.oam[(address - 0xFE00) as usize],
+            0xFEA0..=0xFEFF => 0x00, // DMG reads zero here while OAM isn't blocked
+            KEY1 if self.model.is_cgb() => {
+                0x7E | if self.double_speed { 0x80 } else { 0 } | self.speed_switch_armed as u8
+            }
+            VBK if self.model.is_cgb() => 0xFE | self.vram_bank,
+            0xFF51..=0xFF55 if self.model.is_cgb() => self.hdma.read(address),
+            DMA => self.io.read(address),
+            0xFF40..=0xFF4B => self.ppu.read(address),
+            0xFF68..=0xFF6C if self.model.is_cgb() => self.ppu.read(address),
+            SVBK if self.model.is_cgb() => 0xF8 | self.wram_bank,
+            P1 => self.joypad.read(),
+            0xFF01..=0xFF7F => self.io.read(address),
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize],
+            0xFFFF => self.interrupt_enable,
+        }
     }
     pub fn write_byte(&mut self, address: u16, byte: u8) {
-        self.memory[address as usize] = byte
-    } 
+        let byte = self.observe(address, byte, AccessKind::Write);
+        if self.dma_blocks(address) || self.ppu_blocks(address) {
+            return;
+        }
+        match address {
+            0x0000..=0x7FFF => self.cartridge.write_rom(address, byte),
+            0x8000..=0x9FFF => self.vram[self.vram_index(address - 0x8000)] = byte,
+            0xA000..=0xBFFF => self.cartridge.write_ram(address, byte),
+            0xC000..=0xDFFF => self.wram[self.wram_index(address)] = byte,
+            0xE000..=0xFDFF => self.wram[self.wram_index(address - 0x2000)] = byte,
+            0xFE00..=0xFE9F => self.oam[(address - 0xFE00) as usize] = byte,
+            0xFEA0..=0xFEFF => {}
+            DMA => {
+                self.io.write(address, byte);
+                self.dma.start(byte);
+            }
+            0xFF40..=0xFF4B => {
+                if self.ppu.write(address, byte) {
+                    self.request_interrupt(interrupts::STAT);
+                }
+            }
+            0xFF68..=0xFF6C if self.model.is_cgb() => {
+                self.ppu.write(address, byte);
+            }
+            KEY1 if self.model.is_cgb() => self.speed_switch_armed = byte & 0x01 != 0,
+            VBK if self.model.is_cgb() => self.vram_bank = byte & 0x01,
+            SVBK if self.model.is_cgb() => self.wram_bank = byte & 0x07,
+            0xFF51..=0xFF55 if self.model.is_cgb() => {
+                // a general purpose DMA copies everything now and holds the cpu for it
+                for _ in 0..self.hdma.write(address, byte) {
+                    self.copy_hdma_block();
+                }
+            }
+            P1 => {
+                if self.joypad.write(byte) {
+                    self.request_interrupt(interrupts::JOYPAD);
+                }
+            }
+            0xFF01..=0xFF7F => self.io.write(address, byte),
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize] = byte,
+            0xFFFF => self.interrupt_enable = byte,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn oam_bug_hits_the_row_the_ppu_is_scanning() {
+        for model in [Model::Dmg, Model::Cgb] {
+            let mut bus = MemoryBus::new();
+            bus.set_model(model);
+            for (i, byte) in bus.oam.iter_mut().enumerate() {
+                *byte = i as u8;
+            }
+            let before = bus.oam;
+            bus.trigger_oam_bug(0xFE00, OamBugKind::Write);
+            assert_eq!(bus.oam, before);
+            // 5 m-cycles into mode 2 the ppu is on row 5
+            bus.write_byte(0xFF40, 0x80);
+            bus.tick(20);
+            assert_eq!(bus.oam_scan_row, Some(5));
+            bus.trigger_oam_bug(0xC000, OamBugKind::Write);
+            assert_eq!(bus.oam, before);
+            bus.trigger_oam_bug(0xFEFF, OamBugKind::Write);
+            let corrupted = bus.oam[40..48] != before[40..48];
+            assert_eq!(corrupted, model == Model::Dmg);
+            assert_eq!(bus.oam[..40], before[..40]);
+        }
+    }
+
+    #[test]
+    fn observers_can_replace_what_is_read_and_written() {
+        let mut bus = MemoryBus::new();
+        bus.write_byte(0xC000, 0x12);
+        bus.add_observer(0xC000..=0xC000, Box::new(|access| match access.kind {
+            AccessKind::Read => Some(access.value + 1),
+            _ => None,
+        }));
+        // a later observer sees what the earlier one made of it
+        bus.add_observer(0xC000..=0xC001, Box::new(|access| match access.kind {
+            AccessKind::Write => Some(0x99),
+            _ => Some(access.value * 2),
+        }));
+        assert_eq!(bus.read_byte(0xC000), 0x26);
+        bus.write_byte(0xC001, 0x34);
+        assert_eq!(bus.read_direct(0xC001), 0x99);
+    }
+
+    #[test]
+    fn each_access_is_stamped_with_its_own_m_cycle() {
+        let mut bus = MemoryBus::new();
+        let cycles = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&cycles);
+        bus.add_observer(0xC000..=0xC0FF, Box::new(move |access| {
+            seen.borrow_mut().push(access.cycle);
+            None
+        }));
+        bus.tick(100);
+        bus.fetch_byte(0xC000);
+        bus.read_byte(0xC010);
+        bus.internal_cycle();
+        bus.write_byte(0xC020, 0);
+        bus.tick(16);
+        bus.read_byte(0xC030);
+        assert_eq!(*cycles.borrow(), vec![100, 104, 112, 116]);
+    }
+
+    #[test]
+    fn unusable_area_reads_ff_while_oam_is_blocked() {
+        let mut bus = MemoryBus::new();
+        assert_eq!(bus.read_byte(0xFEA0), 0x00);
+        // turning the lcd on starts line 0 in mode 2
+        bus.write_byte(0xFF40, 0x80);
+        assert_eq!(bus.read_byte(0xFEA0), 0xFF);
+        assert_eq!(bus.read_byte(0xFEFF), 0xFF);
+        bus.write_byte(0xFF40, 0x00);
+        assert_eq!(bus.read_byte(0xFEA0), 0x00);
+    }
+
+    #[test]
+    fn p1_buttons_are_read_only() {
+        let mut bus = MemoryBus::new();
+        bus.write_byte(0xFF00, 0x20);
+        assert_eq!(bus.read_byte(0xFF00), 0xEF);
+        bus.write_byte(0xFF00, 0x00);
+        assert_eq!(bus.read_byte(0xFF00), 0xCF);
+    }
+
+    #[test]
+    fn buttons_show_up_on_the_selected_line_and_interrupt() {
+        let mut bus = MemoryBus::new();
+        bus.write_byte(0xFF00, 0x10); // buttons only
+        bus.set_button(Button::Right, true);
+        assert_eq!((bus.read_byte(0xFF00), bus.read_byte(IF) & interrupts::JOYPAD), (0xDF, 0));
+        bus.set_button(Button::Start, true);
+        assert_eq!((bus.read_byte(0xFF00), bus.read_byte(IF) & interrupts::JOYPAD), (0xD7, interrupts::JOYPAD));
+        // selecting the d-pad brings right down too
+        bus.write_byte(IF, 0);
+        bus.write_byte(0xFF00, 0x00);
+        assert_eq!((bus.read_byte(0xFF00), bus.read_byte(IF) & interrupts::JOYPAD), (0xC6, interrupts::JOYPAD));
+    }
+
+    #[test]
+    fn unusable_area_reads_ff_during_oam_dma() {
+        let mut bus = MemoryBus::new();
+        // one m-cycle of startup, then the first byte moves
+        bus.write_byte(0xFF46, 0xC0);
+        bus.tick(8);
+        assert!(bus.dma_active());
+        assert_eq!(bus.read_byte(0xFEA0), 0xFF);
+    }
+
+    #[test]
+    fn oam_dma_copies_a_byte_per_m_cycle_and_owns_the_bus() {
+        let mut bus = MemoryBus::new();
+        for i in 0..OAM_SIZE as u16 {
+            bus.write_byte(0xC000 + i, i as u8);
+        }
+        bus.write_byte(0xFF46, 0xC0);
+        bus.tick(4 * 160);
+        assert!(bus.dma_active());
+        // the cpu sees the byte the DMA moved last, except in high ram
+        assert_eq!(bus.read_byte(0xC123), 158);
+        bus.write_byte(0xC123, 0x55);
+        bus.write_byte(0xFF80, 0x42);
+        assert_eq!(bus.read_byte(0xFF80), 0x42);
+        bus.tick(4);
+        assert!(!bus.dma_active());
+        assert_eq!(bus.read_byte(0xC123), 0x00);
+        assert!(bus.oam.iter().enumerate().all(|(i, &byte)| byte == i as u8));
+    }
+
+    #[test]
+    fn oam_dma_leaves_the_io_registers_and_ie_reachable() {
+        let mut bus = MemoryBus::new();
+        bus.write_byte(0xC000, 0x11);
+        bus.write_byte(0xD000, 0x22);
+        bus.write_byte(0xFF46, 0xC0);
+        bus.tick(8);
+        bus.write_byte(0xFF47, 0xE4);
+        bus.write_byte(0xFFFF, 0x1F);
+        assert_eq!((bus.read_byte(0xFF47), bus.read_byte(0xFFFF)), (0xE4, 0x1F));
+        // restarting from another page
+        bus.write_byte(0xFF46, 0xD0);
+        bus.tick(4 * 2);
+        assert_eq!(bus.oam[0], 0x22);
+    }
+
+    fn hdma_bus() -> MemoryBus {
+        let mut bus = MemoryBus::new();
+        bus.set_model(Model::Cgb);
+        for i in 0..0x40 {
+            bus.write_byte(0xC000 + i, i as u8 + 1);
+        }
+        bus.write_byte(0xFF51, 0xC0);
+        bus.write_byte(0xFF52, 0x00);
+        bus.write_byte(0xFF53, 0x81); // only bits 0-4 count, so this is 0x8100
+        bus.write_byte(0xFF54, 0x00);
+        bus
+    }
+
+    #[test]
+    fn general_purpose_dma_copies_everything_at_once() {
+        let mut bus = hdma_bus();
+        bus.write_byte(0xFF55, 0x01);
+        assert_eq!(bus.vram[0x100..0x120], bus.wram[..0x20]);
+        assert_eq!(bus.vram[0x120], 0);
+        assert_eq!(bus.read_byte(0xFF55), 0xFF);
+        assert_eq!(bus.take_stall_cycles(), 64);
+    }
+
+    #[test]
+    fn hblank_dma_copies_a_block_per_hblank() {
+        let mut bus = hdma_bus();
+        bus.write_byte(0xFF55, 0x82);
+        assert_eq!(bus.read_byte(0xFF55), 0x02);
+        bus.hblank();
+        assert_eq!(bus.vram[0x100..0x110], bus.wram[..0x10]);
+        assert_eq!(bus.vram[0x110], 0);
+        assert_eq!(bus.read_byte(0xFF55), 0x01);
+        // the registers kept counting, the next block follows on
+        bus.hblank();
+        assert_eq!(bus.vram[0x110..0x120], bus.wram[0x10..0x20]);
+        // writing bit 7 clear stops it
+        bus.write_byte(0xFF55, 0x00);
+        assert_eq!(bus.read_byte(0xFF55) & 0x80, 0x80);
+        bus.hblank();
+        assert_eq!(bus.vram[0x120], 0);
+    }
+
+    #[test]
+    fn cgb_banks_vram_and_wram() {
+        let mut bus = MemoryBus::new();
+        bus.set_model(Model::Cgb);
+        bus.write_byte(0x8000, 0x11);
+        bus.write_byte(0xFF4F, 0x01);
+        assert_eq!(bus.read_byte(0xFF4F), 0xFF);
+        bus.write_byte(0x8000, 0x22);
+        assert_eq!((bus.vram[0], bus.vram(1)[0]), (0x11, 0x22));
+
+        for bank in 1..8 {
+            bus.write_byte(0xFF70, bank);
+            bus.write_byte(0xD000, bank);
+        }
+        bus.write_byte(0xFF70, 0x03);
+        assert_eq!((bus.read_byte(0xFF70), bus.read_byte(0xD000), bus.read_byte(0xF000)), (0xFB, 3, 3));
+        // bank 0 can't be mapped at 0xD000, selecting it gives bank 1
+        bus.write_byte(0xFF70, 0x00);
+        assert_eq!(bus.read_byte(0xD000), 1);
+    }
+
+    #[test]
+    fn dmg_has_no_banking() {
+        let mut bus = MemoryBus::new();
+        bus.write_byte(0xFF4F, 0x01);
+        bus.write_byte(0xFF70, 0x03);
+        bus.write_byte(0x8000, 0x11);
+        bus.write_byte(0xD000, 0x22);
+        assert_eq!((bus.vram[0], bus.vram(1)[0]), (0x11, 0x00));
+        assert_eq!(bus.wram[WRAM_BANK_SIZE], 0x22);
+        assert_eq!(bus.read_byte(0xFF4F), 0xFF);
+    }
 }
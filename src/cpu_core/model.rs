@@ -0,0 +1,16 @@
+// which hardware revision is being emulated, a few quirks only exist on some of them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model {
+    Dmg, // original gameboy (also covers the pocket)
+    Cgb, // gameboy color
+}
+
+impl Model {
+    pub fn is_dmg(&self) -> bool {
+        *self == Model::Dmg
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        *self == Model::Cgb
+    }
+}
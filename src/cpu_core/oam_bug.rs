@@ -0,0 +1,128 @@
+/* DMG OAM corruption bug
+   While the ppu is in mode 2 it reads one OAM row (8 bytes, 4 words) per m-cycle.
+   If the cpu puts an address in 0xFE00-0xFEFF on the bus at the same time (including
+   through the 16 bit inc/dec unit) the row the ppu is reading gets mangled with the row
+   before it. Row 0 is never affected. The patterns below are the ones documented in pan docs.
+
+   OAM rows
+   ┌------┬------┬------┬------┐
+   | w0   | w1   | w2   | w3   |  row 0  0xFE00
+   ├------┼------┼------┼------┤
+   | w0   | w1   | w2   | w3   |  row 1  0xFE08
+   └------┴------┴------┴------┘  ... 20 rows total
+*/
+
+pub const OAM_ROWS: usize = 20;
+const ROW_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OamBugKind {
+    Read,
+    Write, // also used for a plain 16 bit increase/decrease
+    ReadIncrease, // a read and an increase/decrease in the same m-cycle (ld a,[hli], pop)
+}
+
+pub fn corrupt(oam: &mut [u8], row: usize, kind: OamBugKind) {
+    if row == 0 || row >= OAM_ROWS {
+        return;
+    }
+    match kind {
+        OamBugKind::Write => {
+            let a = word(oam, row, 0);
+            let b = word(oam, row - 1, 0);
+            let c = word(oam, row - 1, 2);
+            set_word(oam, row, 0, ((a ^ c) & (b ^ c)) ^ c);
+            copy_tail_from_previous(oam, row);
+        }
+        OamBugKind::Read => {
+            let a = word(oam, row, 0);
+            let b = word(oam, row - 1, 0);
+            let c = word(oam, row - 1, 2);
+            set_word(oam, row, 0, b | (a & c));
+            copy_tail_from_previous(oam, row);
+        }
+        OamBugKind::ReadIncrease => {
+            // the extra step only happens away from the first four rows and the last one
+            if (4..OAM_ROWS - 1).contains(&row) {
+                let a = word(oam, row - 2, 0);
+                let b = word(oam, row - 1, 0);
+                let c = word(oam, row, 0);
+                let d = word(oam, row - 1, 2);
+                set_word(oam, row - 1, 0, (b & (a | c | d)) | (a & c & d));
+                let previous = row - 1;
+                let mut copy = [0u8; ROW_SIZE];
+                copy.copy_from_slice(&oam[previous * ROW_SIZE..(previous + 1) * ROW_SIZE]);
+                oam[row * ROW_SIZE..(row + 1) * ROW_SIZE].copy_from_slice(&copy);
+                oam[(row - 2) * ROW_SIZE..(row - 1) * ROW_SIZE].copy_from_slice(&copy);
+            }
+            corrupt(oam, row, OamBugKind::Read);
+        }
+    }
+}
+
+fn word(oam: &[u8], row: usize, index: usize) -> u16 {
+    let offset = row * ROW_SIZE + index * 2;
+    u16::from_le_bytes([oam[offset], oam[offset + 1]])
+}
+
+fn set_word(oam: &mut [u8], row: usize, index: usize, value: u16) {
+    let offset = row * ROW_SIZE + index * 2;
+    oam[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+// words 1-3 of the row are replaced with words 1-3 of the row before it
+fn copy_tail_from_previous(oam: &mut [u8], row: usize) {
+    let start = row * ROW_SIZE;
+    oam.copy_within(start - ROW_SIZE + 2..start, start + 2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every byte different, so copied words are easy to spot
+    fn oam() -> Vec<u8> {
+        (0..OAM_ROWS * ROW_SIZE).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn write_mixes_the_first_word_and_copies_the_rest() {
+        let mut oam = oam();
+        corrupt(&mut oam, 5, OamBugKind::Write);
+        let (a, b, c) = (word(&self::oam(), 5, 0), word(&oam, 4, 0), word(&oam, 4, 2));
+        assert_eq!(word(&oam, 5, 0), ((a ^ c) & (b ^ c)) ^ c);
+        assert_eq!(oam[42..48], oam[34..40]);
+        assert_eq!(oam[..40], self::oam()[..40]);
+    }
+
+    #[test]
+    fn read_mixes_the_first_word_the_other_way() {
+        let mut oam = oam();
+        corrupt(&mut oam, 1, OamBugKind::Read);
+        let (a, b, c) = (word(&self::oam(), 1, 0), word(&oam, 0, 0), word(&oam, 0, 2));
+        assert_eq!(word(&oam, 1, 0), b | (a & c));
+        assert_eq!(oam[10..16], oam[2..8]);
+    }
+
+    #[test]
+    fn row_0_is_never_touched() {
+        let mut oam = oam();
+        for kind in [OamBugKind::Read, OamBugKind::Write, OamBugKind::ReadIncrease] {
+            corrupt(&mut oam, 0, kind);
+        }
+        assert_eq!(oam, self::oam());
+    }
+
+    #[test]
+    fn read_increase_also_spreads_over_the_rows_before() {
+        let mut oam = oam();
+        corrupt(&mut oam, 6, OamBugKind::ReadIncrease);
+        // rows 4 and 6 get a copy of the mixed row 5 before the read corrupts row 6
+        assert_eq!(oam[32..40], oam[40..48]);
+        let mut near_start = self::oam();
+        corrupt(&mut near_start, 3, OamBugKind::ReadIncrease);
+        let mut plain_read = self::oam();
+        corrupt(&mut plain_read, 3, OamBugKind::Read);
+        assert_eq!(near_start, plain_read);
+    }
+}
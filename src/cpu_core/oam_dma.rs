@@ -0,0 +1,76 @@
+/* OAM DMA
+   Writing XX to 0xFF46 copies XX00 - XX9F into OAM, one byte per m-cycle. The copy starts
+   one m-cycle after the write, so it takes 162 m-cycles from the write to the end.
+
+   While bytes are moving the DMA unit owns the cpu's bus. Anything the cpu reads outside
+   of the I/O registers, high ram and IE gets the byte the DMA is moving instead, OAM reads
+   0xFF and writes to those places are dropped. That's why games run their DMA routine
+   from high ram.
+
+   Writing 0xFF46 again during a transfer restarts it from the new source. The old transfer
+   keeps going through the startup m-cycle, so OAM is never unblocked in between.
+   Sources above 0xDF00 read work ram through the echo mirror.
+*/
+use crate::cpu_core::memory::OAM_SIZE;
+
+const START_DELAY: u8 = 1; // m-cycles between the write and the first byte
+
+pub struct OamDma {
+    source: u16,
+    index: usize, // next byte to copy, OAM_SIZE when idle
+    pending: Option<(u16, u8)>, // a requested transfer and the m-cycles until it starts
+    value: u8, // last byte that went over the bus
+}
+
+impl Default for OamDma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OamDma {
+    pub fn new() -> Self {
+        OamDma {
+            source: 0,
+            index: OAM_SIZE,
+            pending: None,
+            value: 0xFF,
+        }
+    }
+
+    pub fn start(&mut self, high_byte: u8) {
+        self.pending = Some(((high_byte as u16) << 8, START_DELAY));
+    }
+
+    // true while bytes are being copied and the cpu is locked out
+    pub fn active(&self) -> bool {
+        self.index < OAM_SIZE
+    }
+
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    pub fn set_value(&mut self, value: u8) {
+        self.value = value
+    }
+
+    // advance one m-cycle, gives the source address and OAM index to copy this cycle
+    pub fn step(&mut self) -> Option<(u16, usize)> {
+        if let Some((source, delay)) = self.pending {
+            if delay == 0 {
+                self.source = source;
+                self.index = 0;
+                self.pending = None;
+            } else {
+                self.pending = Some((source, delay - 1));
+            }
+        }
+        if !self.active() {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+        Some((self.source + index as u16, index))
+    }
+}
@@ -0,0 +1,94 @@
+/* Hooks for tools that watch the cpu's memory accesses (tracers, cheats, achievements,
+   code/data loggers). Each observer covers an address range and gets called with every
+   read, write or instruction fetch the cpu makes inside it. DMA transfers aren't cpu
+   accesses and don't show up here.
+
+   A callback can hand back a value to use instead of the one in the access: for reads and
+   fetches that's what the cpu gets, for writes it's what gets stored (cheats, patches).
+   When several observers cover an address they run in the order they were added, each one
+   seeing the value the ones before it left.
+
+   Reads happen through &self, so callbacks sit in a RefCell. A callback can't reach the
+   bus it's attached to, it only sees the access.
+   With nothing attached the bus only pays for an is_empty check.
+*/
+use std::cell::RefCell;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Fetch, // opcode and operand bytes read by the cpu
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryAccess {
+    pub address: u16,
+    pub value: u8, // what was read, or what was written
+    pub cycle: u64, // bus t-cycle count at the start of the m-cycle it happened in
+    pub kind: AccessKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObserverId(usize);
+
+// returns Some to replace the value, None to leave it alone
+pub type Callback = Box<dyn FnMut(&MemoryAccess) -> Option<u8>>;
+
+struct Observer {
+    id: ObserverId,
+    range: RangeInclusive<u16>,
+    callback: RefCell<Callback>,
+}
+
+pub struct Observers {
+    list: Vec<Observer>,
+    next_id: usize,
+}
+
+impl Default for Observers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Observers {
+    pub fn new() -> Self {
+        Observers {
+            list: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn add(&mut self, range: RangeInclusive<u16>, callback: Callback) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        self.list.push(Observer { id, range, callback: RefCell::new(callback) });
+        id
+    }
+
+    // false if there was no observer with that id
+    pub fn remove(&mut self, id: ObserverId) -> bool {
+        let before = self.list.len();
+        self.list.retain(|observer| observer.id != id);
+        self.list.len() != before
+    }
+
+    // the value after every observer in range has had its say
+    pub fn notify(&self, access: &MemoryAccess) -> u8 {
+        let mut access = *access;
+        for observer in &self.list {
+            if observer.range.contains(&access.address) {
+                if let Some(value) = (observer.callback.borrow_mut())(&access) {
+                    access.value = value;
+                }
+            }
+        }
+        access.value
+    }
+}
@@ -4,8 +4,9 @@ pub enum RegistersU8 {
     A,B,C,D,E,F,H,L,
 }
 
+#[derive(Clone, Copy)]
 pub enum RegistersU16 {
-    BC,HL,
+    BC,DE,HL,
 }
 
 pub struct RegisterBank {
@@ -19,6 +20,12 @@ pub struct RegisterBank {
     l: u8
 }
 
+impl Default for RegisterBank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RegisterBank {
     
     pub fn new() -> Self {
@@ -62,6 +69,7 @@ impl RegisterBank {
     pub fn get_register_u16(&self, register: RegistersU16) -> u16 {
         match register {
             RegistersU16::BC => (self.b as u16) << 8 | self.c as u16,
+            RegistersU16::DE => (self.d as u16) << 8 | self.e as u16,
             RegistersU16::HL => (self.h as u16) << 8 | self.l as u16, 
         }
     }
@@ -72,6 +80,10 @@ impl RegisterBank {
                 self.b = ((value & 0xFF00) >> 8) as u8;
                 self.c = (value & 0xFF) as u8;
             }
+            RegistersU16::DE => {
+                self.d = ((value & 0xFF00) >> 8) as u8;
+                self.e = (value & 0xFF) as u8;
+            }
             RegistersU16::HL => {
                 self.h = ((value & 0xFF00) >> 8) as u8;
                 self.c = (value & 0xFF) as u8;
@@ -103,11 +115,11 @@ pub const SUBTRACT_FLAG_BYTE_POSITION: u8 = 6;
 pub const HALF_CARRY_FLAG_BYTE_POSITION: u8 = 5;
 pub const CARRY_FLAG_BYTE_POSITION: u8 = 4;
 
-impl std::convert::Into<u8> for &FlagsRegister {
-    fn into(self) -> u8 {
-        (if self.zero       { 1 } else { 0 }) << ZERO_FLAG_BYTE_POSITION |
-        (if self.subtract   { 1 } else { 0 }) << SUBTRACT_FLAG_BYTE_POSITION | 
-        (if self.half_carry { 1 } else { 0 }) << HALF_CARRY_FLAG_BYTE_POSITION |
-        (if self.carry      { 1 } else { 0 }) << CARRY_FLAG_BYTE_POSITION 
+impl std::convert::From<&FlagsRegister> for u8 {
+    fn from(flags: &FlagsRegister) -> u8 {
+        (if flags.zero       { 1 } else { 0 }) << ZERO_FLAG_BYTE_POSITION |
+        (if flags.subtract   { 1 } else { 0 }) << SUBTRACT_FLAG_BYTE_POSITION | 
+        (if flags.half_carry { 1 } else { 0 }) << HALF_CARRY_FLAG_BYTE_POSITION |
+        (if flags.carry      { 1 } else { 0 }) << CARRY_FLAG_BYTE_POSITION 
     }
 }
@@ -0,0 +1,47 @@
+/* How many t-cycles each instruction takes. Conditional jumps, calls and returns are listed
+   with the time they take when the condition fails, branch_extra gives what a taken
+   branch adds on top. Unused opcodes are listed as 4, they hang the cpu anyway. */
+
+#[rustfmt::skip]
+const CYCLES: [u8; 256] = [
+//  x0  x1  x2  x3  x4  x5  x6  x7  x8  x9  xA  xB  xC  xD  xE  xF
+     4, 12,  8,  8,  4,  4,  8,  4, 20,  8,  8,  8,  4,  4,  8,  4, // 0x
+     4, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4, // 1x
+     8, 12,  8,  8,  4,  4,  8,  4,  8,  8,  8,  8,  4,  4,  8,  4, // 2x
+     8, 12,  8,  8, 12, 12, 12,  4,  8,  8,  8,  8,  4,  4,  8,  4, // 3x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 4x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 5x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 6x
+     8,  8,  8,  8,  8,  8,  4,  8,  4,  4,  4,  4,  4,  4,  8,  4, // 7x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 8x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 9x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // Ax
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // Bx
+     8, 12, 12, 16, 12, 16,  8, 16,  8, 16, 12,  4, 12, 24,  8, 16, // Cx
+     8, 12, 12,  4, 12, 16,  8, 16,  8, 16, 12,  4, 12,  4,  8, 16, // Dx
+    12, 12,  8,  4,  4, 16,  8, 16, 16,  4, 16,  4,  4,  4,  8, 16, // Ex
+    12, 12,  8,  4,  4, 16,  8, 16, 12,  8, 16,  4,  4,  4,  8, 16, // Fx
+];
+
+pub fn cycles(opcode: u8, prefixed: bool) -> u32 {
+    if !prefixed {
+        return CYCLES[opcode as usize] as u32;
+    }
+    // cb prefixed ones only differ for the (hl) column, BIT doesn't write back so it's shorter
+    match (opcode & 0x07, opcode) {
+        (6, 0x40..=0x7F) => 12,
+        (6, _) => 16,
+        _ => 8,
+    }
+}
+
+// added when a conditional branch is taken
+pub fn branch_extra(opcode: u8) -> u32 {
+    match opcode {
+        0x20 | 0x28 | 0x30 | 0x38 => 4, // jr cc
+        0xC2 | 0xCA | 0xD2 | 0xDA => 4, // jp cc
+        0xC4 | 0xCC | 0xD4 | 0xDC => 12, // call cc
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => 12, // ret cc
+        _ => 0,
+    }
+}
@@ -0,0 +1,43 @@
+pub mod cpu_core {
+    pub mod cpu;
+    pub mod memory;
+    pub mod registers;
+    pub mod instruction;
+    pub mod flags_register;
+    pub mod io_registers;
+    pub mod model;
+    pub mod oam_bug;
+    pub mod oam_dma;
+    pub mod hdma;
+    pub mod observer;
+    pub mod timing;
+    pub mod interrupts;
+    pub mod joypad;
+
+}
+pub mod ppu_core {
+    pub mod ppu;
+    pub mod tiles;
+    pub mod objects;
+    pub mod fifo;
+    pub mod palettes;
+}
+pub mod cartridge {
+    pub mod header;
+    pub mod mapper;
+    pub mod rom_only;
+    pub mod mbc1;
+    pub mod mbc2;
+    pub mod mbc3;
+    pub mod mbc5;
+    pub mod mbc6;
+    pub mod mbc7;
+    pub mod mmm01;
+    pub mod huc1;
+    pub mod huc3;
+    pub mod tama5;
+    pub mod camera;
+    pub mod unlicensed;
+    pub mod rtc;
+    pub mod save;
+}
@@ -1,29 +1,187 @@
-use cpu_core::cpu;
+use emulator::cartridge::camera;
+use emulator::cartridge::header::{self, CgbFlag};
+use emulator::cartridge::mapper::{self, CartridgeEvent, HostInput};
+use emulator::cartridge::save::SaveFile;
+use emulator::cartridge::unlicensed::UnlicensedMapper;
+use emulator::cpu_core::cpu;
+use emulator::cpu_core::model::Model;
+use emulator::ppu_core::ppu::Renderer;
 use std::fs;
 use std::env;
 use std::io::Error;
-mod cpu_core {
-    pub mod cpu;
-    pub mod memory;
-    pub mod registers;
-    pub mod instruction;
-    pub mod flags_register;
-
-}
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 /* 0x0000 to 0x00FF are the ROM  */
 // the prefix byte is 0xCB
 
+// set by Ctrl-C or a SIGTERM, the run loop stops at the next instruction and writes the save
+static STOP: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+fn stop_on_signals() {
+    const SIGINT: i32 = 2;
+    const SIGTERM: i32 = 15;
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+    // only touches an atomic, which is all a signal handler may safely do
+    extern "C" fn handler(_: i32) {
+        STOP.store(true, Ordering::Relaxed);
+    }
+    unsafe {
+        signal(SIGINT, handler);
+        signal(SIGTERM, handler);
+    }
+}
+
+#[cfg(not(unix))]
+fn stop_on_signals() {}
+
 fn main() {
-    
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("rom-info") => rom_info(&args[1..]),
+        Some(_) => run(&args),
+        None => run(&["cpu_instrs.gb".to_string()]),
+    }
+}
+
+// emulator <rom> [--mapper wisdom-tree|sachen-mmc1|sachen-mmc2] [--camera-image <file.pgm>]
+//               [--renderer scanline|fifo] [--model dmg|cgb] [--steps <n>]
+// the model defaults to CGB for carts whose header says they support it
+fn run(args: &[String]) {
+    let rom_path = &args[0];
+    let mut mapper_override = None;
+    let mut camera_image = None;
+    let mut step_limit = None;
+    let mut renderer = Renderer::Scanline;
+    let mut model_override = None;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match (option.as_str(), options.next()) {
+            ("--mapper", Some(name)) => match UnlicensedMapper::from_name(name) {
+                Some(mapper) => mapper_override = Some(mapper),
+                None => panic!("unknown mapper: {name}"),
+            },
+            ("--camera-image", Some(path)) => camera_image = Some(path),
+            ("--renderer", Some(name)) => match name.as_str() {
+                "scanline" => renderer = Renderer::Scanline,
+                "fifo" => renderer = Renderer::Fifo,
+                _ => panic!("unknown renderer: {name}"),
+            },
+            ("--model", Some(name)) => match name.as_str() {
+                "dmg" => model_override = Some(Model::Dmg),
+                "cgb" => model_override = Some(Model::Cgb),
+                _ => panic!("unknown model: {name}"),
+            },
+            ("--steps", Some(count)) => match count.parse::<u64>() {
+                Ok(count) => step_limit = Some(count),
+                Err(_) => panic!("bad step count: {count}"),
+            },
+            _ => panic!("unknown option: {option}"),
+        }
+    }
+
     let mut cpu = cpu::CPU::new();
-    let a = env::current_dir();
-    match load_file("cpu_instrs.gb") {
-        Ok(data) => cpu.load_rom(data),
+    cpu.bus_mut().ppu_mut().set_renderer(renderer);
+    let data = match load_file(rom_path) {
+        Ok(data) => data,
         Err(e) => panic!("Failed to load file: {:?}", e),
+    };
+    let header = header::parse(&data).ok();
+    let battery = header.as_ref().is_some_and(|header| header.cartridge_type.battery);
+    let model = model_override.unwrap_or(match header.map(|header| header.cgb_flag) {
+        Some(CgbFlag::CgbSupported | CgbFlag::CgbOnly) => Model::Cgb,
+        _ => Model::Dmg,
+    });
+    cpu.bus_mut().set_model(model);
+    cpu.load_cartridge(mapper::load_with_override(data, mapper_override));
+    // only carts with a battery keep their ram once the power is off
+    let mut save_file = None;
+    if battery {
+        match SaveFile::open(Path::new(rom_path), cpu.bus_mut().cartridge_mut()) {
+            Ok(file) => save_file = Some(file),
+            Err(e) => eprintln!("Failed to load save: {e}"),
+        }
     }
-    
-    loop {
-        cpu.step();
+    if let Some(path) = camera_image {
+        match camera::load_pgm(path) {
+            Ok((pixels, width, height)) => {
+                cpu.bus_mut().cartridge_mut().host_input(HostInput::CameraImage { pixels, width, height })
+            }
+            Err(e) => panic!("Failed to load camera image: {:?}", e),
+        }
+    }
+
+    // runs until killed with Ctrl-C / SIGTERM or after the given number of instructions
+    // a panic is caught long enough to write the save, then carries on
+    stop_on_signals();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut steps = 0;
+        while step_limit.is_none_or(|limit| steps < limit) && !STOP.load(Ordering::Relaxed) {
+            cpu.step();
+            steps += 1;
+            for event in cpu.take_events() {
+                match event {
+                    // keep running so the frozen screen stays up, like on hardware
+                    cpu::CpuEvent::Lockup { pc, opcode } => {
+                        eprintln!("CPU locked up: illegal opcode 0x{opcode:02x} at 0x{pc:04x}")
+                    }
+                }
+            }
+            for event in cpu.bus_mut().cartridge_mut().take_events() {
+                match event {
+                    CartridgeEvent::Rumble(on) => println!("rumble {}", if on { "on" } else { "off" }),
+                    CartridgeEvent::Infrared(on) => println!("infrared {}", if on { "on" } else { "off" }),
+                    CartridgeEvent::Tone(tone) => println!("speaker tone {tone}"),
+                    CartridgeEvent::Alarm => println!("alarm"),
+                }
+            }
+            if let Some(file) = save_file.as_mut() {
+                if let Err(e) = file.tick(cpu.bus().cartridge(), cpu.bus().cycles()) {
+                    eprintln!("Failed to write {}: {e}", file.path().display());
+                }
+            }
+        }
+    }));
+
+    if let Some(file) = save_file.as_mut() {
+        if let Err(e) = file.flush(cpu.bus().cartridge()) {
+            eprintln!("Failed to write {}: {e}", file.path().display());
+        }
+    }
+    if let Err(panic) = result {
+        panic::resume_unwind(panic);
+    }
+}
+
+// print the header of every rom given, without loading any of them
+fn rom_info(paths: &[String]) {
+    if paths.is_empty() {
+        eprintln!("usage: emulator rom-info <rom>...");
+        std::process::exit(2);
+    }
+    let mut failed = false;
+    for path in paths {
+        println!("{path}");
+        match load_file(path) {
+            Ok(data) => match header::validate(&data) {
+                Ok(report) => println!("{report}"),
+                Err(e) => {
+                    println!("error: {e}");
+                    failed = true;
+                }
+            },
+            Err(e) => {
+                println!("error: {e}");
+                failed = true;
+            }
+        }
+        println!();
+    }
+    if failed {
+        std::process::exit(1);
     }
 }
 
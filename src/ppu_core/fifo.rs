@@ -0,0 +1,398 @@
+/* Pixel FIFO renderer
+   Mode 3 the way the hardware does it, one dot at a time:
+
+   background fetcher  ┌------┬------┬------┬---------------┐
+   (2 dots per step)   | tile | low  | high | push when the |──> bg fifo ──┐
+                       └------┴------┴------┴ fifo is empty ┘              ├─ mix ─> pixel
+   object fetch (6 dots, merged into the object fifo) ──────> obj fifo ────┘
+
+   - The line starts with a 6 dot fetch whose result is thrown away, so the first pixel
+     comes out on dot 13 and an undisturbed line takes 172 dots.
+   - The first SCX % 8 pixels are popped and dropped (fine scroll).
+   - When the next pixel reaches WX - 7 on a line where the window is on, the bg fifo is
+     emptied and the fetcher restarts on the window map.
+   - When the next pixel reaches an object's X, output stops, the bg fetcher finishes the
+     tile it's on and the object's row is fetched in 6 dots. Object pixels only go into
+     empty or transparent object fifo slots, so objects fetched first (smaller X, then
+     OAM order) win, which is the DMG priority. With the CGB's OAM order priority an
+     opaque pixel also takes over the slot from an object further down OAM.
+   - On CGB the fetcher reads the map attributes from vram bank 1 along with the tile number.
+   Pixels come out as colour numbers with their attributes, the ppu looks up the colour.
+   Registers are read when they're used, so writes in the middle of mode 3 land on the
+   pixel the fetcher or the mixer is at when they happen.
+
+   Not checked against dmg-acid2 yet: the cpu can't run it (most opcodes still don't decode),
+   so only the cases in the tests below are known to come out right.
+*/
+use std::collections::VecDeque;
+
+use crate::cpu_core::memory::VRAM_SIZE;
+use crate::ppu_core::objects::{self, Object};
+use crate::ppu_core::ppu::{
+    Pixel, LCDC_BG_ENABLE, LCDC_BG_MAP, LCDC_OBJ_ENABLE, LCDC_OBJ_SIZE, LCDC_WINDOW_ENABLE, LCDC_WINDOW_MAP,
+    SCREEN_WIDTH,
+};
+use crate::ppu_core::tiles::{self, BG_ATTR_X_FLIP, MAP_SIZE};
+
+const WARMUP_DOTS: u8 = 6;
+const OBJECT_FETCH_DOTS: u8 = 6;
+
+// the ppu registers as they are on the current dot
+pub struct Registers {
+    pub lcdc: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub wx: u8,
+    pub window_line: u8,
+    pub window_triggered: bool,
+    pub cgb: bool,
+    pub oam_priority: bool, // CGB objects sorted by OAM position only
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FetchStep {
+    Tile,
+    Low,
+    High,
+    Push,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BgPixel {
+    colour: u8,
+    attributes: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ObjectPixel {
+    colour: u8,
+    attributes: u8,
+    index: usize, // OAM position of the object it came from
+}
+
+pub struct Fifo {
+    bg: VecDeque<BgPixel>,
+    obj: VecDeque<ObjectPixel>,
+    step: FetchStep,
+    step_dots: u8, // dots spent on the current step
+    warmup: u8,
+    fetch_x: u8, // tile column the fetcher is on, counted from the start of the line or window
+    tile: u8,
+    attributes: u8,
+    low: u8,
+    high: u8,
+    x: usize, // next pixel on screen
+    discard: u8,
+    in_window: bool,
+    window_drawn: bool,
+    objects: Vec<Object>,
+    fetched: Vec<bool>,
+    object_fetch: Option<(usize, u8)>, // index into objects and dots spent, counting this one
+}
+
+impl Default for Fifo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fifo {
+    pub fn new() -> Self {
+        Fifo {
+            bg: VecDeque::with_capacity(8),
+            obj: VecDeque::with_capacity(8),
+            step: FetchStep::Tile,
+            step_dots: 0,
+            warmup: WARMUP_DOTS,
+            fetch_x: 0,
+            tile: 0,
+            attributes: 0,
+            low: 0,
+            high: 0,
+            x: 0,
+            discard: 0,
+            in_window: false,
+            window_drawn: false,
+            objects: Vec::new(),
+            fetched: Vec::new(),
+            object_fetch: None,
+        }
+    }
+
+    pub fn start_line(&mut self, objects: Vec<Object>, scx: u8) {
+        self.bg.clear();
+        self.obj.clear();
+        self.step = FetchStep::Tile;
+        self.step_dots = 0;
+        self.warmup = WARMUP_DOTS;
+        self.fetch_x = 0;
+        self.x = 0;
+        self.discard = scx % 8;
+        self.in_window = false;
+        self.window_drawn = false;
+        self.fetched = vec![false; objects.len()];
+        self.objects = objects;
+        self.object_fetch = None;
+    }
+
+    pub fn done(&self) -> bool {
+        self.x == SCREEN_WIDTH
+    }
+
+    // whether the window showed up on this line, its line counter moves if so
+    pub fn window_drawn(&self) -> bool {
+        self.window_drawn
+    }
+
+    // one dot of mode 3, gives back the screen x of a finished pixel and what's there
+    pub fn step(&mut self, regs: &Registers, vram: &[u8]) -> Option<(usize, Pixel)> {
+        if self.warmup > 0 {
+            self.warmup -= 1;
+            return None;
+        }
+        if self.object_fetch.is_some() {
+            self.step_object_fetch(regs, vram);
+            return None;
+        }
+        self.step_fetcher(regs, vram);
+        self.check_window(regs);
+        // objects are only looked at once there's a pixel waiting to come out
+        if self.discard == 0 && !self.bg.is_empty() && self.start_object_fetch(regs) {
+            return None;
+        }
+        self.output(regs)
+    }
+
+    fn step_fetcher(&mut self, regs: &Registers, vram: &[u8]) {
+        if self.step == FetchStep::Push {
+            if self.bg.is_empty() {
+                let row = (self.low, self.high);
+                let attributes = self.attributes;
+                let flip = attributes & BG_ATTR_X_FLIP != 0;
+                self.bg.extend((0..8).map(|x| BgPixel {
+                    colour: tiles::pixel(row, if flip { 7 - x } else { x }),
+                    attributes,
+                }));
+                self.fetch_x = self.fetch_x.wrapping_add(1);
+                self.step = FetchStep::Tile;
+            }
+            return;
+        }
+        self.step_dots += 1;
+        if self.step_dots < 2 {
+            return;
+        }
+        self.step_dots = 0;
+        self.step = match self.step {
+            FetchStep::Tile => {
+                let (map, column, _) = self.map_position(regs);
+                self.tile = vram[map + column];
+                self.attributes = if regs.cgb { vram[VRAM_SIZE + map + column] } else { 0 };
+                FetchStep::Low
+            }
+            FetchStep::Low => {
+                (self.low, _) = self.tile_row(regs, vram);
+                FetchStep::High
+            }
+            _ => {
+                (_, self.high) = self.tile_row(regs, vram);
+                FetchStep::Push
+            }
+        };
+    }
+
+    // vram offset of the map row, the column in it and the pixel row in the tile
+    fn map_position(&self, regs: &Registers) -> (usize, usize, usize) {
+        let (map_bit, x, y) = if self.in_window {
+            (LCDC_WINDOW_MAP, self.fetch_x as usize, regs.window_line as usize)
+        } else {
+            let y = regs.ly.wrapping_add(regs.scy) as usize;
+            (LCDC_BG_MAP, (regs.scx / 8) as usize + self.fetch_x as usize, y)
+        };
+        let map = tiles::map_address(regs.lcdc & map_bit != 0) + (y / 8) * MAP_SIZE;
+        (map, x % MAP_SIZE, y % 8)
+    }
+
+    fn tile_row(&self, regs: &Registers, vram: &[u8]) -> (u8, u8) {
+        let (_, _, row) = self.map_position(regs);
+        tiles::bg_tile_row(vram, regs.lcdc, self.tile, self.attributes, row)
+    }
+
+    fn check_window(&mut self, regs: &Registers) {
+        let window_on = regs.lcdc & LCDC_WINDOW_ENABLE != 0 && regs.window_triggered && regs.wx <= 166;
+        if self.in_window || !window_on || self.x + 7 < regs.wx as usize {
+            return;
+        }
+        self.in_window = true;
+        self.window_drawn = true;
+        self.bg.clear();
+        self.fetch_x = 0;
+        self.step = FetchStep::Tile;
+        self.step_dots = 0;
+        // with WX below 7 the window starts partly off the left edge
+        self.discard = (self.x + 7 - regs.wx as usize) as u8;
+    }
+
+    // true while output has to wait for an object
+    fn start_object_fetch(&mut self, regs: &Registers) -> bool {
+        if regs.lcdc & LCDC_OBJ_ENABLE == 0 {
+            return false;
+        }
+        // with objects turned on mid line, the ones output has already gone past are lost
+        for (fetched, object) in self.fetched.iter_mut().zip(&self.objects) {
+            if self.x > 0 && object.x as usize <= self.x {
+                *fetched = true;
+            }
+        }
+        let next = (0..self.objects.len())
+            .filter(|&i| !self.fetched[i] && self.objects[i].x < 168 && self.objects[i].x as usize <= self.x + 8)
+            .min_by_key(|&i| (self.objects[i].x, self.objects[i].index));
+        let Some(index) = next else {
+            return false;
+        };
+        // the bg fetcher gets to (almost) finish its tile first, the object fetch overlaps
+        // its last dot, so the wait is 0 to 5 dots on top of the fetch's 6
+        let almost_done = self.step == FetchStep::High && self.step_dots == 1;
+        if self.step == FetchStep::Push || almost_done {
+            self.object_fetch = Some((index, 1));
+        }
+        true
+    }
+
+    fn step_object_fetch(&mut self, regs: &Registers, vram: &[u8]) {
+        let Some((index, dots)) = self.object_fetch else {
+            return;
+        };
+        if dots + 1 < OBJECT_FETCH_DOTS {
+            self.object_fetch = Some((index, dots + 1));
+            return;
+        }
+        self.object_fetch = None;
+        self.fetched[index] = true;
+        let object = self.objects[index];
+        let height = if regs.lcdc & LCDC_OBJ_SIZE != 0 { 16 } else { 8 };
+        let row = object.row(vram, regs.ly, height, regs.cgb);
+        // objects hanging off the left edge lose the pixels that are already past
+        let skip = self.x + 8 - object.x as usize;
+        while self.obj.len() < 8 {
+            self.obj.push_back(ObjectPixel { colour: 0, attributes: 0, index: 0 });
+        }
+        for (slot, &colour) in self.obj.iter_mut().zip(&row[skip..]) {
+            let earlier_in_oam = regs.oam_priority && colour != 0 && object.index < slot.index;
+            if slot.colour == 0 || earlier_in_oam {
+                *slot = ObjectPixel { colour, attributes: object.attributes, index: object.index };
+            }
+        }
+    }
+
+    fn output(&mut self, regs: &Registers) -> Option<(usize, Pixel)> {
+        let mut bg = self.bg.pop_front()?;
+        let obj = self.obj.pop_front();
+        if self.discard > 0 {
+            self.discard -= 1;
+            return None;
+        }
+        // only the DMG blanks the bg with LCDC bit 0, the CGB takes away its priority instead
+        if !regs.cgb && regs.lcdc & LCDC_BG_ENABLE == 0 {
+            bg.colour = 0;
+        }
+        let mut pixel = Pixel::Bg { colour: bg.colour, attributes: bg.attributes };
+        if let Some(obj) = obj {
+            let shown = objects::above_bg(obj.attributes, bg.colour, bg.attributes, regs.lcdc, regs.cgb);
+            if obj.colour != 0 && shown && regs.lcdc & LCDC_OBJ_ENABLE != 0 {
+                pixel = Pixel::Obj { colour: obj.colour, attributes: obj.attributes };
+            }
+        }
+        let x = self.x;
+        self.x += 1;
+        Some((x, pixel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppu_core::ppu::LCDC_ENABLE;
+
+    fn registers(lcdc: u8, scx: u8) -> Registers {
+        Registers {
+            lcdc: LCDC_ENABLE | lcdc,
+            scy: 0,
+            scx,
+            ly: 0,
+            wx: 0,
+            window_line: 0,
+            window_triggered: false,
+            cgb: false,
+            oam_priority: false,
+        }
+    }
+
+    fn object(x: u8) -> Object {
+        Object { y: 16, x, tile: 0, attributes: 0, index: 0 }
+    }
+
+    // dots until the line is out, the finished line goes in frame
+    fn draw(fifo: &mut Fifo, regs: &Registers, vram: &[u8], frame: &mut [Option<Pixel>]) -> u32 {
+        let mut dots = 0;
+        while !fifo.done() {
+            if let Some((x, pixel)) = fifo.step(regs, vram) {
+                frame[x] = Some(pixel);
+            }
+            dots += 1;
+            assert!(dots < 1000, "line never finished");
+        }
+        dots
+    }
+
+    #[test]
+    fn plain_line_takes_172_dots() {
+        let vram = vec![0; VRAM_SIZE * 2];
+        let mut fifo = Fifo::new();
+        let mut frame = [None; SCREEN_WIDTH];
+        fifo.start_line(Vec::new(), 0);
+        assert_eq!(draw(&mut fifo, &registers(LCDC_BG_ENABLE, 0), &vram, &mut frame), 172);
+        assert!(frame.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn fine_scroll_and_objects_stretch_the_line() {
+        let vram = vec![0; VRAM_SIZE * 2];
+        let mut fifo = Fifo::new();
+        let mut frame = [None; SCREEN_WIDTH];
+        fifo.start_line(Vec::new(), 3);
+        assert_eq!(draw(&mut fifo, &registers(LCDC_BG_ENABLE, 3), &vram, &mut frame), 175);
+        fifo.start_line(vec![object(13)], 0);
+        let regs = registers(LCDC_BG_ENABLE | LCDC_OBJ_ENABLE, 0);
+        assert_eq!(draw(&mut fifo, &regs, &vram, &mut frame), 172 + objects::penalty(&[object(13)], 0));
+    }
+
+    #[test]
+    fn object_pixels_land_on_their_x() {
+        let mut vram = vec![0; VRAM_SIZE * 2];
+        vram[0] = 0x80; // tile 0 row 0, leftmost pixel colour 1
+        let mut fifo = Fifo::new();
+        let mut frame = [None; SCREEN_WIDTH];
+        fifo.start_line(vec![Object { tile: 0, ..object(20) }], 0);
+        // bg tiles use 0x8800 addressing so they stay blank
+        draw(&mut fifo, &registers(LCDC_BG_ENABLE | LCDC_OBJ_ENABLE, 0), &vram, &mut frame);
+        assert_eq!(frame[12], Some(Pixel::Obj { colour: 1, attributes: 0 }));
+        assert_eq!(frame[13], Some(Pixel::Bg { colour: 0, attributes: 0 }));
+    }
+
+    #[test]
+    fn objects_turned_on_mid_line_skip_the_ones_already_passed() {
+        let vram = vec![0; VRAM_SIZE * 2];
+        let mut fifo = Fifo::new();
+        let mut frame = [None; SCREEN_WIDTH];
+        fifo.start_line(vec![object(16), object(120)], 0);
+        let regs = registers(LCDC_BG_ENABLE, 0);
+        for _ in 0..100 {
+            fifo.step(&regs, &vram);
+        }
+        let regs = registers(LCDC_BG_ENABLE | LCDC_OBJ_ENABLE, 0);
+        draw(&mut fifo, &regs, &vram, &mut frame);
+        assert!(fifo.fetched.iter().all(|&fetched| fetched));
+    }
+}
@@ -0,0 +1,182 @@
+/* Objects (sprites)
+   OAM holds 40 entries of 4 bytes:
+     0  y + 16
+     1  x + 8
+     2  tile number (always 0x8000 addressing, bit 0 ignored for 8x16 objects)
+     3  attributes: 7 behind bg colours 1-3, 6 y flip, 5 x flip, 4 DMG palette (OBP1 when set),
+        3 CGB vram bank, 2-0 CGB palette
+
+   During mode 2 the ppu picks the first 10 entries, in OAM order, whose rows cover the
+   line. X doesn't matter for the pick, so objects off the sides still use up slots.
+   On DMG the object with the smaller X wins where two overlap, OAM order breaks ties.
+   The CGB goes by OAM order alone, unless OPRI (0xFF6C) bit 0 asks for the DMG rule.
+   A pixel with colour 0 is transparent and lets the next object (or the bg) through.
+
+   Against the bg/window an object loses to bg colours 1-3 when its own bit 7 is set, or on
+   CGB when the map attribute's bit 7 is. With LCDC bit 0 clear the CGB ignores both and
+   always puts objects on top (the DMG hides the bg instead, so that comes out the same).
+
+   Every object on the line stretches mode 3 by 6 to 11 dots: 6 for the fetch, plus the
+   time to finish the background tile it lands in if that tile hasn't been fetched yet.
+*/
+use crate::cpu_core::memory::VRAM_SIZE;
+use crate::ppu_core::ppu::LCDC_BG_ENABLE;
+use crate::ppu_core::tiles::{self, BG_ATTR_PRIORITY};
+
+pub const OBJECTS_PER_LINE: usize = 10;
+const OAM_ENTRIES: usize = 40;
+
+pub const ATTR_BEHIND_BG: u8 = 0x80;
+pub const ATTR_Y_FLIP: u8 = 0x40;
+pub const ATTR_X_FLIP: u8 = 0x20;
+pub const ATTR_PALETTE: u8 = 0x10;
+pub const ATTR_BANK: u8 = 0x08;
+pub const ATTR_CGB_PALETTE: u8 = 0x07;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Object {
+    pub y: u8, // as stored, screen y + 16
+    pub x: u8, // as stored, screen x + 8
+    pub tile: u8,
+    pub attributes: u8,
+    pub index: usize, // position in OAM
+}
+
+impl Object {
+    // the colour numbers of this object's row on line ly, leftmost pixel first
+    pub fn row(&self, vram: &[u8], ly: u8, height: u8, cgb: bool) -> [u8; 8] {
+        // the scan may have picked the object with the other height, LCDC bit 2 can change
+        // before the row is fetched, so only the rows that fit the current height count
+        let mut row = (ly + 16 - self.y) as usize & (height as usize - 1);
+        if self.attributes & ATTR_Y_FLIP != 0 {
+            row = height as usize - 1 - row;
+        }
+        let tile = if height == 16 { self.tile & 0xFE } else { self.tile } as usize;
+        let bank = if cgb && self.attributes & ATTR_BANK != 0 { VRAM_SIZE } else { 0 };
+        let bytes = tiles::tile_row(vram, bank + tile * 16, row);
+        let mut pixels = [0; 8];
+        for (x, pixel) in pixels.iter_mut().enumerate() {
+            let x = if self.attributes & ATTR_X_FLIP != 0 { 7 - x } else { x };
+            *pixel = tiles::pixel(bytes, x);
+        }
+        pixels
+    }
+}
+
+// the objects on line ly, in OAM order
+pub fn scan(oam: &[u8], ly: u8, height: u8) -> Vec<Object> {
+    let line = ly as u16 + 16;
+    let mut objects = Vec::with_capacity(OBJECTS_PER_LINE);
+    for index in 0..OAM_ENTRIES {
+        let entry = &oam[index * 4..index * 4 + 4];
+        let y = entry[0] as u16;
+        if line >= y && line < y + height as u16 {
+            objects.push(Object { y: entry[0], x: entry[1], tile: entry[2], attributes: entry[3], index });
+            if objects.len() == OBJECTS_PER_LINE {
+                break;
+            }
+        }
+    }
+    objects
+}
+
+// whether an opaque object pixel shows over a bg/window pixel
+pub fn above_bg(attributes: u8, bg_colour: u8, bg_attributes: u8, lcdc: u8, cgb: bool) -> bool {
+    if bg_colour == 0 || (cgb && lcdc & LCDC_BG_ENABLE == 0) {
+        return true;
+    }
+    attributes & ATTR_BEHIND_BG == 0 && bg_attributes & BG_ATTR_PRIORITY == 0
+}
+
+// extra mode 3 dots the objects on a line cost
+pub fn penalty(objects: &[Object], scx: u8) -> u32 {
+    let mut fetched_tiles = Vec::new();
+    let mut dots = 0;
+    let mut sorted: Vec<&Object> = objects.iter().filter(|object| object.x < 168).collect();
+    sorted.sort_by_key(|object| object.x);
+    for object in sorted {
+        dots += 6;
+        // objects hanging off the left edge wait for the whole first tile
+        let position = object.x as u32 + (scx % 8) as u32;
+        let tile = position / 8;
+        if !fetched_tiles.contains(&tile) {
+            fetched_tiles.push(tile);
+            let pixels_left_in_tile = 7 - position % 8;
+            dots += if object.x == 0 { 5 } else { pixels_left_in_tile.saturating_sub(2) };
+        }
+    }
+    dots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(y: u8, attributes: u8) -> Object {
+        Object { y, x: 8, tile: 0, attributes, index: 0 }
+    }
+
+    // tile 0 in vram, row n has its leftmost n + 1 pixels at colour 1
+    fn vram() -> Vec<u8> {
+        let mut vram = vec![0; VRAM_SIZE * 2];
+        for row in 0..16 {
+            vram[row * 2] = 0xFFu8 << (7 - row % 8);
+        }
+        vram
+    }
+
+    fn lit(row: [u8; 8]) -> usize {
+        row.iter().filter(|&&colour| colour == 1).count()
+    }
+
+    #[test]
+    fn scan_picks_the_first_ten_in_oam_order() {
+        let mut oam = [0u8; 160];
+        for index in 0..12 {
+            oam[index * 4] = 16;
+            oam[index * 4 + 1] = 200; // off screen still takes a slot
+        }
+        let objects = scan(&oam, 0, 8);
+        assert_eq!(objects.len(), OBJECTS_PER_LINE);
+        assert_eq!(objects.last().unwrap().index, 9);
+        assert!(scan(&oam, 8, 8).is_empty());
+        assert_eq!(scan(&oam, 8, 16).len(), OBJECTS_PER_LINE);
+    }
+
+    #[test]
+    fn y_flip_counts_from_the_bottom() {
+        let vram = vram();
+        assert_eq!(lit(object(16, 0).row(&vram, 2, 8, false)), 3);
+        assert_eq!(lit(object(16, ATTR_Y_FLIP).row(&vram, 2, 8, false)), 6);
+    }
+
+    #[test]
+    fn row_fits_a_height_that_shrank_after_the_scan() {
+        // picked as 8x16 on its second half, drawn after LCDC bit 2 went back to 8x8
+        let vram = vram();
+        let tall = object(8, ATTR_Y_FLIP);
+        assert_eq!(lit(tall.row(&vram, 2, 8, false)), 6);
+        assert_eq!(lit(object(8, 0).row(&vram, 2, 8, false)), 3);
+    }
+
+    #[test]
+    fn behind_bg_only_loses_to_colours_1_to_3() {
+        assert!(above_bg(ATTR_BEHIND_BG, 0, 0, LCDC_BG_ENABLE, false));
+        assert!(!above_bg(ATTR_BEHIND_BG, 2, 0, LCDC_BG_ENABLE, false));
+        assert!(!above_bg(0, 2, BG_ATTR_PRIORITY, LCDC_BG_ENABLE, true));
+        // the CGB's master priority
+        assert!(above_bg(ATTR_BEHIND_BG, 2, BG_ATTR_PRIORITY, 0, true));
+    }
+
+    #[test]
+    fn penalty_is_6_to_11_dots_per_object() {
+        let at = |x| Object { y: 16, x, tile: 0, attributes: 0, index: 0 };
+        assert_eq!(penalty(&[], 0), 0);
+        assert_eq!(penalty(&[at(0)], 0), 11);
+        assert_eq!(penalty(&[at(8)], 0), 11);
+        assert_eq!(penalty(&[at(13)], 0), 6);
+        // a second object in the same tile only pays for its own fetch
+        assert_eq!(penalty(&[at(8), at(9)], 0), 17);
+        assert_eq!(penalty(&[at(168)], 0), 0);
+    }
+}
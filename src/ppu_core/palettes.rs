@@ -0,0 +1,103 @@
+/* CGB palette RAM
+   The CGB has 8 background and 8 object palettes of 4 colours each, kept in two 64 byte
+   memories the cpu reaches through an index register and a data register:
+     0xFF68 BCPS  7 auto increment, 5-0 byte index   0xFF69 BCPD  byte at the index
+     0xFF6A OCPS                                     0xFF6B OCPD
+   A colour is two bytes, little endian, 15 bit: red in bits 0-4, green 5-9, blue 10-14.
+   With auto increment on every data write moves the index to the next byte (wrapping at 64),
+   reads don't move it. The ppu holds on to the data registers during mode 3.
+
+   The frame buffer uses the same 15 bit colours, so DMG shades are turned into greys.
+*/
+const PALETTE_RAM_SIZE: usize = 64;
+
+pub const WHITE: u16 = 0x7FFF;
+const DMG_GREYS: [u16; 4] = [WHITE, 0x56B5, 0x294A, 0x0000];
+
+pub struct PaletteRam {
+    data: [u8; PALETTE_RAM_SIZE],
+    index: u8,
+    auto_increment: bool,
+}
+
+impl Default for PaletteRam {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PaletteRam {
+    pub fn new() -> Self {
+        // the boot rom leaves everything white
+        PaletteRam { data: [0xFF; PALETTE_RAM_SIZE], index: 0, auto_increment: false }
+    }
+
+    pub fn read_spec(&self) -> u8 {
+        0x40 | if self.auto_increment { 0x80 } else { 0 } | self.index
+    }
+
+    pub fn write_spec(&mut self, value: u8) {
+        self.index = value & 0x3F;
+        self.auto_increment = value & 0x80 != 0;
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data[self.index as usize]
+    }
+
+    // a blocked write (mode 3) is lost but still moves the index
+    pub fn write_data(&mut self, value: u8, blocked: bool) {
+        if !blocked {
+            self.data[self.index as usize] = value;
+        }
+        if self.auto_increment {
+            self.index = (self.index + 1) & 0x3F;
+        }
+    }
+
+    // colour number (0-3) of a palette (0-7) as a 15 bit colour
+    pub fn colour(&self, palette: u8, colour: u8) -> u16 {
+        let address = (palette as usize & 0x07) * 8 + colour as usize * 2;
+        u16::from_le_bytes([self.data[address], self.data[address + 1]]) & 0x7FFF
+    }
+}
+
+// a DMG shade (0 white to 3 black) as a 15 bit colour
+pub fn dmg_colour(shade: u8) -> u16 {
+    DMG_GREYS[shade as usize & 0x03]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_writes_auto_increment_and_wrap() {
+        let mut palettes = PaletteRam::new();
+        palettes.write_spec(0x80 | 0x3F);
+        palettes.write_data(0x1F, false);
+        palettes.write_data(0x00, false);
+        assert_eq!(palettes.read_spec(), 0xC1);
+        assert_eq!(palettes.colour(7, 3), 0x1FFF); // the last byte is the high one
+        assert_eq!(palettes.colour(0, 0), 0x7F00);
+    }
+
+    #[test]
+    fn reads_and_plain_writes_leave_the_index() {
+        let mut palettes = PaletteRam::new();
+        palettes.write_spec(0x02);
+        palettes.write_data(0x34, false);
+        palettes.read_data();
+        assert_eq!((palettes.read_spec(), palettes.read_data()), (0x42, 0x34));
+    }
+
+    #[test]
+    fn blocked_writes_are_lost_but_move_the_index() {
+        let mut palettes = PaletteRam::new();
+        palettes.write_spec(0x80);
+        palettes.write_data(0x00, true);
+        palettes.write_data(0x00, false);
+        assert_eq!(palettes.colour(0, 0), 0x00FF);
+        assert_eq!(palettes.read_spec(), 0xC2);
+    }
+}
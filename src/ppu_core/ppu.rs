@@ -0,0 +1,763 @@
+/* Scanline PPU
+   Every line is 456 dots (t-cycles at normal speed), 154 lines make a frame.
+
+     dots      0 .. 80          80 .. 80+n         .. 456
+             ┌-----------------┬------------------┬------------------┐
+   line 0    | mode 2 OAM scan | mode 3 drawing   | mode 0 HBlank    |
+   ...       |                 |                  |                  |
+   line 143  |                 |                  |                  |
+             ├-----------------┴------------------┴------------------┤
+   line 144  | mode 1 VBlank                                         |
+   ...       |                                                       |
+   line 153  |                                                       |
+             └-------------------------------------------------------┘
+   Drawing takes 172 dots plus SCX % 8 for the pixels thrown away at the start of the line
+   plus the object fetches (see objects.rs), HBlank gets whatever is left. A line is drawn into the frame buffer in one go when
+   drawing ends, with the registers as they are at that point, so scroll changes made
+   between lines show up on the right line.
+
+   The window is drawn from the screen column WX - 7 onwards once LY has matched WY in
+   this frame. It has its own line counter that only moves on lines where the window was
+   actually drawn, so hiding it for a few lines doesn't skip any of it.
+
+   0xFF40 LCDC  7 lcd on, 6 window map, 5 window on, 4 tile data, 3 bg map, 2 obj size,
+                1 obj on, 0 bg/window on
+   0xFF41 STAT  6 LYC=LY int, 5 mode 2 int, 4 mode 1 int, 3 mode 0 int, 2 LYC=LY, 1-0 mode
+   0xFF42 SCY   0xFF43 SCX   0xFF44 LY (read only)   0xFF45 LYC
+   0xFF47 BGP   0xFF48 OBP0  0xFF49 OBP1   0xFF4A WY   0xFF4B WX
+
+   On CGB the colours come from palette RAM (see palettes.rs) and the maps have attributes
+   in vram bank 1 (see tiles.rs), objects pick their palette and tile bank themselves.
+   0xFF68 BCPS  0xFF69 BCPD  0xFF6A OCPS  0xFF6B OCPD
+   0xFF6C OPRI  0 objects by OAM position (CGB), 1 by X like the DMG
+
+   The frame buffer holds 15 bit colours on both, DMG shades come out as greys.
+
+   That's the scanline renderer. Renderer::Fifo swaps mode 3 for a dot by dot model of the
+   fetchers and the pixel FIFO (see fifo.rs), where mode 3 is as long as the pixels take.
+
+   The STAT interrupt fires when any enabled source turns on while none were on before,
+   so sources that overlap block each other (the "STAT blocking" quirk).
+   Turning the lcd off puts LY at 0 and the mode at 0 and stops everything, it starts
+   again from the top of line 0 when turned back on. The screen shows blank while it's off.
+*/
+use crate::cpu_core::memory::VRAM_SIZE;
+use crate::ppu_core::fifo::{self, Fifo};
+use crate::ppu_core::objects::{self, Object, ATTR_CGB_PALETTE, ATTR_PALETTE};
+use crate::ppu_core::palettes::{self, PaletteRam, WHITE};
+use crate::ppu_core::tiles::{self, BG_ATTR_PALETTE, BG_ATTR_X_FLIP, MAP_SIZE};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+const DOTS_PER_LINE: u32 = 456;
+const OAM_SCAN_DOTS: u32 = 80;
+const DRAWING_DOTS: u32 = 172;
+const LINES_PER_FRAME: u8 = 154;
+
+pub const LCDC_ENABLE: u8 = 0x80;
+pub const LCDC_WINDOW_MAP: u8 = 0x40;
+pub const LCDC_WINDOW_ENABLE: u8 = 0x20;
+pub const LCDC_TILE_DATA: u8 = 0x10;
+pub const LCDC_BG_MAP: u8 = 0x08;
+pub const LCDC_OBJ_SIZE: u8 = 0x04;
+pub const LCDC_OBJ_ENABLE: u8 = 0x02;
+pub const LCDC_BG_ENABLE: u8 = 0x01;
+
+const STAT_LYC_INT: u8 = 0x40;
+const STAT_OAM_INT: u8 = 0x20;
+const STAT_VBLANK_INT: u8 = 0x10;
+const STAT_HBLANK_INT: u8 = 0x08;
+const STAT_LYC_EQUAL: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Renderer {
+    Scanline, // a whole line at once, cheap
+    Fifo, // pixel by pixel, for mid line effects
+}
+
+// a finished pixel before it goes through a palette
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pixel {
+    Bg { colour: u8, attributes: u8 }, // attributes from the CGB map, 0 on DMG
+    Obj { colour: u8, attributes: u8 }, // attributes from OAM
+}
+
+// what happened during a tick, for the bus to act on
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PpuEvents {
+    pub vblank_interrupt: bool,
+    pub stat_interrupt: bool,
+    pub hblank: bool, // a visible line's HBlank started (HBlank DMA runs here)
+}
+
+pub struct Ppu {
+    lcdc: u8,
+    stat: u8, // only the interrupt enable bits, the rest is worked out on read
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    opri: u8,
+    bg_palettes: PaletteRam,
+    obj_palettes: PaletteRam,
+    cgb: bool,
+    mode: Mode,
+    dot: u32, // position in the current line
+    drawing_dots: u32, // length of mode 3 on this line
+    stat_line: bool, // the ORed STAT sources, interrupts fire on its rising edge
+    frame: Vec<u16>, // SCREEN_WIDTH * SCREEN_HEIGHT 15 bit colours
+    frame_ready: bool,
+    window_triggered: bool, // LY matched WY at some point this frame
+    window_line: u8, // next window row to draw
+    line_colours: [u8; SCREEN_WIDTH], // background/window colour numbers of the line being drawn
+    line_attributes: [u8; SCREEN_WIDTH], // and their map attributes
+    objects: Vec<Object>, // picked for the current line by the OAM scan
+    renderer: Renderer,
+    fifo: Fifo,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Ppu {
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            opri: 0,
+            bg_palettes: PaletteRam::new(),
+            obj_palettes: PaletteRam::new(),
+            cgb: false,
+            mode: Mode::HBlank,
+            dot: 0,
+            drawing_dots: DRAWING_DOTS,
+            stat_line: false,
+            frame: vec![WHITE; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+            window_triggered: false,
+            window_line: 0,
+            line_colours: [0; SCREEN_WIDTH],
+            line_attributes: [0; SCREEN_WIDTH],
+            objects: Vec::new(),
+            renderer: Renderer::Scanline,
+            fifo: Fifo::new(),
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF40 => self.lcdc,
+            0xFF41 => {
+                let lyc_equal = if self.ly == self.lyc { STAT_LYC_EQUAL } else { 0 };
+                0x80 | self.stat | lyc_equal | self.mode as u8
+            }
+            0xFF42 => self.scy,
+            0xFF43 => self.scx,
+            0xFF44 => self.ly,
+            0xFF45 => self.lyc,
+            0xFF47 => self.bgp,
+            0xFF48 => self.obp0,
+            0xFF49 => self.obp1,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
+            // palette data is out of reach while the ppu is drawing
+            0xFF68 => self.bg_palettes.read_spec(),
+            0xFF69 if self.mode != Mode::Drawing => self.bg_palettes.read_data(),
+            0xFF6A => self.obj_palettes.read_spec(),
+            0xFF6B if self.mode != Mode::Drawing => self.obj_palettes.read_data(),
+            0xFF6C => 0xFE | self.opri,
+            _ => 0xFF,
+        }
+    }
+
+    // returns true if the write raised the STAT interrupt
+    pub fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0xFF40 => {
+                let was_on = self.enabled();
+                self.lcdc = value;
+                if was_on && !self.enabled() {
+                    self.turn_off();
+                } else if !was_on && self.enabled() {
+                    self.turn_on();
+                }
+            }
+            0xFF41 => self.stat = value & 0x78,
+            0xFF42 => self.scy = value,
+            0xFF43 => self.scx = value,
+            0xFF45 => self.lyc = value,
+            0xFF47 => self.bgp = value,
+            0xFF48 => self.obp0 = value,
+            0xFF49 => self.obp1 = value,
+            0xFF4A => self.wy = value,
+            0xFF4B => self.wx = value,
+            0xFF68 => self.bg_palettes.write_spec(value),
+            0xFF69 => self.bg_palettes.write_data(value, self.mode == Mode::Drawing),
+            0xFF6A => self.obj_palettes.write_spec(value),
+            0xFF6B => self.obj_palettes.write_data(value, self.mode == Mode::Drawing),
+            0xFF6C => self.opri = value & 0x01,
+            _ => {} // LY is read only
+        }
+        self.update_stat_line()
+    }
+
+    // takes effect from the next line
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer
+    }
+
+    // CGB colours, attributes and priorities, the bus only sends the CGB registers when on
+    pub fn set_cgb(&mut self, cgb: bool) {
+        self.cgb = cgb
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.lcdc & LCDC_ENABLE != 0
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    pub fn frame(&self) -> &[u16] {
+        &self.frame
+    }
+
+    // true once per finished frame, when VBlank starts
+    pub fn take_frame_ready(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    // the OAM row being read during mode 2, one per m-cycle
+    pub fn oam_scan_row(&self) -> Option<usize> {
+        match self.mode {
+            Mode::OamScan if self.enabled() => Some((self.dot / 4) as usize),
+            _ => None,
+        }
+    }
+
+    // the cpu can't see OAM during modes 2 and 3, or vram during mode 3
+    pub fn oam_blocked(&self) -> bool {
+        matches!(self.mode, Mode::OamScan | Mode::Drawing)
+    }
+
+    pub fn vram_blocked(&self) -> bool {
+        self.mode == Mode::Drawing
+    }
+
+    // cycles are dots, the bus halves cpu cycles in double speed mode before passing them on.
+    // vram is both banks, bank 1 is only looked at on CGB
+    pub fn tick(&mut self, cycles: u32, vram: &[u8], oam: &[u8]) -> PpuEvents {
+        let mut events = PpuEvents::default();
+        if !self.enabled() {
+            return events;
+        }
+        for _ in 0..cycles {
+            self.step_dot(vram, oam, &mut events);
+        }
+        events
+    }
+
+    fn step_dot(&mut self, vram: &[u8], oam: &[u8], events: &mut PpuEvents) {
+        self.dot += 1;
+        if self.dot == DOTS_PER_LINE {
+            self.dot = 0;
+            self.ly = (self.ly + 1) % LINES_PER_FRAME;
+            if self.ly == SCREEN_HEIGHT as u8 {
+                self.mode = Mode::VBlank;
+                self.frame_ready = true;
+                events.vblank_interrupt = true;
+            } else if self.ly < SCREEN_HEIGHT as u8 {
+                self.start_line();
+            }
+        } else if self.mode == Mode::OamScan && self.dot == OAM_SCAN_DOTS {
+            self.start_drawing(oam);
+        } else if self.mode == Mode::Drawing {
+            if !self.step_drawing(vram) {
+                return;
+            }
+            self.mode = Mode::HBlank;
+            events.hblank = true;
+        } else {
+            return;
+        }
+        events.stat_interrupt |= self.update_stat_line();
+    }
+
+    fn start_drawing(&mut self, oam: &[u8]) {
+        self.mode = Mode::Drawing;
+        match self.renderer {
+            Renderer::Scanline => {
+                self.objects = if self.lcdc & LCDC_OBJ_ENABLE != 0 {
+                    objects::scan(oam, self.ly, self.object_height())
+                } else {
+                    Vec::new()
+                };
+                self.drawing_dots = DRAWING_DOTS + (self.scx % 8) as u32 + objects::penalty(&self.objects, self.scx);
+            }
+            // the fifo checks the object enable bit as it goes, so the scan always runs
+            Renderer::Fifo => self.fifo.start_line(objects::scan(oam, self.ly, self.object_height()), self.scx),
+        }
+    }
+
+    // one dot of mode 3, true when the line is finished
+    fn step_drawing(&mut self, vram: &[u8]) -> bool {
+        match self.renderer {
+            Renderer::Scanline => {
+                if self.dot < OAM_SCAN_DOTS + self.drawing_dots {
+                    return false;
+                }
+                self.render_line(vram);
+                true
+            }
+            Renderer::Fifo => {
+                let registers = fifo::Registers {
+                    lcdc: self.lcdc,
+                    scy: self.scy,
+                    scx: self.scx,
+                    ly: self.ly,
+                    wx: self.wx,
+                    window_line: self.window_line,
+                    window_triggered: self.window_triggered,
+                    cgb: self.cgb,
+                    oam_priority: self.oam_priority(),
+                };
+                if let Some((x, pixel)) = self.fifo.step(&registers, vram) {
+                    self.frame[self.ly as usize * SCREEN_WIDTH + x] = self.colour(pixel);
+                }
+                if !self.fifo.done() {
+                    return false;
+                }
+                if self.fifo.window_drawn() {
+                    self.window_line += 1;
+                }
+                true
+            }
+        }
+    }
+
+    // the stat sources after a change, true on a rising edge
+    fn update_stat_line(&mut self) -> bool {
+        if !self.enabled() {
+            self.stat_line = false;
+            return false;
+        }
+        let line = (self.stat & STAT_LYC_INT != 0 && self.ly == self.lyc)
+            || (self.stat & STAT_OAM_INT != 0 && self.mode == Mode::OamScan)
+            || (self.stat & STAT_VBLANK_INT != 0 && self.mode == Mode::VBlank)
+            || (self.stat & STAT_HBLANK_INT != 0 && self.mode == Mode::HBlank);
+        let rising = line && !self.stat_line;
+        self.stat_line = line;
+        rising
+    }
+
+    fn turn_off(&mut self) {
+        self.ly = 0;
+        self.dot = 0;
+        self.mode = Mode::HBlank;
+        self.stat_line = false;
+        self.frame.fill(WHITE);
+        self.frame_ready = true;
+    }
+
+    fn turn_on(&mut self) {
+        self.ly = 0;
+        self.dot = 0;
+        self.start_line();
+    }
+
+    fn start_line(&mut self) {
+        if self.ly == 0 {
+            self.window_triggered = false;
+            self.window_line = 0;
+        }
+        if self.ly == self.wy {
+            self.window_triggered = true;
+        }
+        self.mode = Mode::OamScan;
+    }
+
+    fn object_height(&self) -> u8 {
+        if self.lcdc & LCDC_OBJ_SIZE != 0 { 16 } else { 8 }
+    }
+
+    fn oam_priority(&self) -> bool {
+        self.cgb && self.opri & 0x01 == 0
+    }
+
+    fn colour(&self, pixel: Pixel) -> u16 {
+        match pixel {
+            Pixel::Bg { colour, attributes } if self.cgb => {
+                self.bg_palettes.colour(attributes & BG_ATTR_PALETTE, colour)
+            }
+            Pixel::Bg { colour, .. } => palettes::dmg_colour(tiles::shade(self.bgp, colour)),
+            Pixel::Obj { colour, attributes } if self.cgb => {
+                self.obj_palettes.colour(attributes & ATTR_CGB_PALETTE, colour)
+            }
+            Pixel::Obj { colour, attributes } => {
+                let palette = if attributes & ATTR_PALETTE != 0 { self.obp1 } else { self.obp0 };
+                palettes::dmg_colour(tiles::shade(palette, colour))
+            }
+        }
+    }
+
+    fn render_line(&mut self, vram: &[u8]) {
+        self.render_background(vram);
+        let start = self.ly as usize * SCREEN_WIDTH;
+        for x in 0..SCREEN_WIDTH {
+            let pixel = Pixel::Bg { colour: self.line_colours[x], attributes: self.line_attributes[x] };
+            self.frame[start + x] = self.colour(pixel);
+        }
+        self.render_objects(vram);
+    }
+
+    fn render_objects(&mut self, vram: &[u8]) {
+        // highest priority first: smaller X, then earlier in OAM (or only OAM order on CGB)
+        let mut order: Vec<&Object> = self.objects.iter().collect();
+        if self.oam_priority() {
+            order.sort_by_key(|object| object.index);
+        } else {
+            order.sort_by_key(|object| (object.x, object.index));
+        }
+        let start = self.ly as usize * SCREEN_WIDTH;
+        // the first opaque object pixel wins, even if it then hides behind the bg
+        let mut claimed = [false; SCREEN_WIDTH];
+        for object in order {
+            let row = object.row(vram, self.ly, self.object_height(), self.cgb);
+            for (i, &colour) in row.iter().enumerate() {
+                let x = object.x as usize + i;
+                if colour == 0 || !(8..SCREEN_WIDTH + 8).contains(&x) || claimed[x - 8] {
+                    continue;
+                }
+                let x = x - 8;
+                claimed[x] = true;
+                let (bg_colour, bg_attributes) = (self.line_colours[x], self.line_attributes[x]);
+                if !objects::above_bg(object.attributes, bg_colour, bg_attributes, self.lcdc, self.cgb) {
+                    continue;
+                }
+                self.frame[start + x] = self.colour(Pixel::Obj { colour, attributes: object.attributes });
+            }
+        }
+    }
+
+    // fills line_colours with the background and the window over it
+    fn render_background(&mut self, vram: &[u8]) {
+        // with LCDC bit 0 clear the DMG shows neither, everything is colour 0.
+        // the CGB still draws them, they just lose their priority over objects
+        if !self.cgb && self.lcdc & LCDC_BG_ENABLE == 0 {
+            self.line_colours = [0; SCREEN_WIDTH];
+            self.line_attributes = [0; SCREEN_WIDTH];
+            return;
+        }
+        let y = self.ly.wrapping_add(self.scy) as usize;
+        let map = tiles::map_address(self.lcdc & LCDC_BG_MAP != 0);
+        for x in 0..SCREEN_WIDTH {
+            let map_x = (x + self.scx as usize) % 256;
+            (self.line_colours[x], self.line_attributes[x]) = self.map_pixel(vram, map, map_x, y);
+        }
+
+        let window_x = self.wx as usize;
+        if self.lcdc & LCDC_WINDOW_ENABLE == 0 || !self.window_triggered || window_x > 166 {
+            return;
+        }
+        let map = tiles::map_address(self.lcdc & LCDC_WINDOW_MAP != 0);
+        let first = window_x.saturating_sub(7);
+        for x in first..SCREEN_WIDTH {
+            let window_y = self.window_line as usize;
+            (self.line_colours[x], self.line_attributes[x]) = self.map_pixel(vram, map, x + 7 - window_x, window_y);
+        }
+        self.window_line += 1;
+    }
+
+    // colour number and attributes at (x, y) of a 256x256 tile map
+    fn map_pixel(&self, vram: &[u8], map: usize, x: usize, y: usize) -> (u8, u8) {
+        let entry = map + (y / 8) * MAP_SIZE + x / 8;
+        let attributes = if self.cgb { vram[VRAM_SIZE + entry] } else { 0 };
+        let row = tiles::bg_tile_row(vram, self.lcdc, vram[entry], attributes, y % 8);
+        let x = if attributes & BG_ATTR_X_FLIP != 0 { 7 - x % 8 } else { x % 8 };
+        (tiles::pixel(row, x), attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu_core::memory::OAM_SIZE;
+    use crate::ppu_core::objects::ATTR_Y_FLIP;
+
+    const BLACK: u16 = 0x0000;
+
+    fn lcd_on(ppu: &mut Ppu, lcdc: u8) {
+        ppu.write(0xFF40, LCDC_ENABLE | lcdc);
+    }
+
+    #[test]
+    fn object_size_change_after_the_scan() {
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let mut ppu = Ppu::new();
+            ppu.set_renderer(renderer);
+            ppu.write(0xFF48, 0xFF);
+            let mut vram = vec![0; VRAM_SIZE * 2];
+            // row 7 of tile 0 has its left half set, tile 1 (the bottom half at 8x16) is solid
+            vram[14] = 0xF0;
+            vram[16..32].fill(0xFF);
+            let mut oam = [0; OAM_SIZE];
+            // a y flipped 8x16 object at x 8 whose lower half covers line 0
+            oam[..4].copy_from_slice(&[8, 16, 0, ATTR_Y_FLIP]);
+            lcd_on(&mut ppu, LCDC_OBJ_ENABLE | LCDC_OBJ_SIZE);
+            ppu.tick(OAM_SCAN_DOTS, &vram, &oam);
+            assert_eq!(ppu.mode(), Mode::Drawing);
+            // drawn as 8x8, so the flipped row is row 7 of tile 0
+            lcd_on(&mut ppu, LCDC_OBJ_ENABLE);
+            ppu.tick(DOTS_PER_LINE - OAM_SCAN_DOTS, &vram, &oam);
+            assert_eq!(ppu.ly(), 1);
+            assert_eq!(&ppu.frame()[7..17], &[WHITE, BLACK, BLACK, BLACK, BLACK, WHITE, WHITE, WHITE, WHITE, WHITE], "{renderer:?}");
+        }
+    }
+
+    #[test]
+    fn modes_follow_the_line_timing() {
+        let mut ppu = Ppu::new();
+        let (vram, oam) = (vec![0; VRAM_SIZE * 2], [0; OAM_SIZE]);
+        lcd_on(&mut ppu, 0);
+        ppu.tick(OAM_SCAN_DOTS - 1, &vram, &oam);
+        assert_eq!(ppu.mode(), Mode::OamScan);
+        ppu.tick(1, &vram, &oam);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+        ppu.tick(DRAWING_DOTS - 1, &vram, &oam);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+        assert!(ppu.tick(1, &vram, &oam).hblank);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+        ppu.tick(DOTS_PER_LINE - OAM_SCAN_DOTS - DRAWING_DOTS, &vram, &oam);
+        assert_eq!((ppu.ly(), ppu.mode()), (1, Mode::OamScan));
+    }
+
+    #[test]
+    fn fine_scroll_makes_drawing_longer() {
+        let mut ppu = Ppu::new();
+        let (vram, oam) = (vec![0; VRAM_SIZE * 2], [0; OAM_SIZE]);
+        ppu.write(0xFF43, 5);
+        lcd_on(&mut ppu, 0);
+        ppu.tick(OAM_SCAN_DOTS + DRAWING_DOTS + 4, &vram, &oam);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+        ppu.tick(1, &vram, &oam);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+    }
+
+    #[test]
+    fn vblank_starts_at_line_144_and_the_frame_wraps_after_153() {
+        let mut ppu = Ppu::new();
+        let (vram, oam) = (vec![0; VRAM_SIZE * 2], [0; OAM_SIZE]);
+        lcd_on(&mut ppu, 0);
+        let events = ppu.tick(DOTS_PER_LINE * SCREEN_HEIGHT as u32, &vram, &oam);
+        assert!(events.vblank_interrupt);
+        assert!(ppu.take_frame_ready());
+        assert_eq!((ppu.ly(), ppu.mode()), (144, Mode::VBlank));
+        ppu.tick(DOTS_PER_LINE * 9, &vram, &oam);
+        assert_eq!((ppu.ly(), ppu.mode()), (153, Mode::VBlank));
+        ppu.tick(DOTS_PER_LINE, &vram, &oam);
+        assert_eq!((ppu.ly(), ppu.mode()), (0, Mode::OamScan));
+    }
+
+    #[test]
+    fn lyc_match_sets_the_flag_and_raises_the_interrupt() {
+        let mut ppu = Ppu::new();
+        let (vram, oam) = (vec![0; VRAM_SIZE * 2], [0; OAM_SIZE]);
+        ppu.write(0xFF45, 2);
+        ppu.write(0xFF41, STAT_LYC_INT);
+        lcd_on(&mut ppu, 0);
+        assert!(!ppu.tick(DOTS_PER_LINE, &vram, &oam).stat_interrupt);
+        assert_eq!(ppu.read(0xFF41) & STAT_LYC_EQUAL, 0);
+        assert!(ppu.tick(DOTS_PER_LINE, &vram, &oam).stat_interrupt);
+        assert_ne!(ppu.read(0xFF41) & STAT_LYC_EQUAL, 0);
+    }
+
+    #[test]
+    fn overlapping_stat_sources_block_each_other() {
+        let mut ppu = Ppu::new();
+        let (vram, oam) = (vec![0; VRAM_SIZE * 2], [0; OAM_SIZE]);
+        ppu.write(0xFF41, STAT_HBLANK_INT | STAT_OAM_INT);
+        lcd_on(&mut ppu, 0);
+        assert!(ppu.tick(OAM_SCAN_DOTS + DRAWING_DOTS, &vram, &oam).stat_interrupt);
+        // HBlank runs straight into the next line's OAM scan, the line never drops
+        assert!(!ppu.tick(DOTS_PER_LINE - OAM_SCAN_DOTS - DRAWING_DOTS, &vram, &oam).stat_interrupt);
+        assert_eq!(ppu.mode(), Mode::OamScan);
+    }
+
+    #[test]
+    fn turning_the_lcd_off_resets_ly_and_stops() {
+        let mut ppu = Ppu::new();
+        let (vram, oam) = (vec![0; VRAM_SIZE * 2], [0; OAM_SIZE]);
+        lcd_on(&mut ppu, 0);
+        ppu.tick(DOTS_PER_LINE * 3 + 100, &vram, &oam);
+        ppu.write(0xFF40, 0);
+        assert_eq!((ppu.ly(), ppu.mode()), (0, Mode::HBlank));
+        ppu.tick(DOTS_PER_LINE * 2, &vram, &oam);
+        assert_eq!(ppu.ly(), 0);
+        lcd_on(&mut ppu, 0);
+        assert_eq!((ppu.ly(), ppu.mode()), (0, Mode::OamScan));
+    }
+
+    // a ppu with the usual palette and vram holding a solid colour 3 tile number 1
+    fn tile_setup(renderer: Renderer) -> (Ppu, Vec<u8>) {
+        let mut ppu = Ppu::new();
+        ppu.set_renderer(renderer);
+        ppu.write(0xFF47, 0xE4);
+        let mut vram = vec![0; VRAM_SIZE * 2];
+        vram[16..32].fill(0xFF);
+        (ppu, vram)
+    }
+
+    #[test]
+    fn scrolled_background() {
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let (mut ppu, mut vram) = tile_setup(renderer);
+            vram[0x1800 + MAP_SIZE + 1] = 1;
+            ppu.write(0xFF42, 8);
+            ppu.write(0xFF43, 8);
+            lcd_on(&mut ppu, LCDC_BG_ENABLE | LCDC_TILE_DATA);
+            ppu.tick(DOTS_PER_LINE, &vram, &[0; OAM_SIZE]);
+            assert_eq!(&ppu.frame()[..9], &[BLACK, BLACK, BLACK, BLACK, BLACK, BLACK, BLACK, BLACK, WHITE], "{renderer:?}");
+        }
+    }
+
+    #[test]
+    fn signed_tile_numbers_count_from_0x9000() {
+        let (mut ppu, mut vram) = tile_setup(Renderer::Scanline);
+        vram[0x1800] = 0x80;
+        vram[0x0800..0x0810].fill(0xFF);
+        lcd_on(&mut ppu, LCDC_BG_ENABLE);
+        ppu.tick(DOTS_PER_LINE, &vram, &[0; OAM_SIZE]);
+        assert_eq!((ppu.frame()[7], ppu.frame()[8]), (BLACK, WHITE));
+    }
+
+    #[test]
+    fn window_starts_at_wx_minus_7() {
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let (mut ppu, mut vram) = tile_setup(renderer);
+            vram[0x1C00] = 1;
+            ppu.write(0xFF4B, 80 + 7);
+            lcd_on(&mut ppu, LCDC_BG_ENABLE | LCDC_TILE_DATA | LCDC_WINDOW_ENABLE | LCDC_WINDOW_MAP);
+            ppu.tick(DOTS_PER_LINE, &vram, &[0; OAM_SIZE]);
+            assert_eq!(&ppu.frame()[79..89], &[WHITE, BLACK, BLACK, BLACK, BLACK, BLACK, BLACK, BLACK, BLACK, WHITE], "{renderer:?}");
+        }
+    }
+
+    #[test]
+    fn hidden_window_lines_are_not_skipped() {
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let (mut ppu, mut vram) = tile_setup(renderer);
+            // only the top two rows of the window tile are black
+            vram[20..32].fill(0);
+            vram[0x1800] = 1;
+            let oam = [0; OAM_SIZE];
+            let window = LCDC_BG_ENABLE | LCDC_TILE_DATA | LCDC_WINDOW_ENABLE;
+            ppu.write(0xFF4B, 7);
+            ppu.write(0xFF42, 0x80); // the background shows an empty part of the map
+            lcd_on(&mut ppu, window);
+            ppu.tick(DOTS_PER_LINE, &vram, &oam);
+            lcd_on(&mut ppu, LCDC_BG_ENABLE | LCDC_TILE_DATA);
+            ppu.tick(DOTS_PER_LINE, &vram, &oam);
+            lcd_on(&mut ppu, window);
+            ppu.tick(DOTS_PER_LINE, &vram, &oam);
+            // line 2 shows the window's second row, not its third
+            let column = |y: usize| ppu.frame()[y * SCREEN_WIDTH];
+            assert_eq!((column(0), column(1), column(2)), (BLACK, WHITE, BLACK), "{renderer:?}");
+        }
+    }
+
+    const RED: u16 = 0x001F;
+    const GREEN: u16 = 0x03E0;
+
+    // colours 1 and 2 of a CGB palette through the spec/data registers
+    fn set_colours(ppu: &mut Ppu, spec: u16, palette: u8) {
+        ppu.write(spec, 0x80 | (palette * 8 + 2));
+        for byte in [RED.to_le_bytes(), GREEN.to_le_bytes()].concat() {
+            ppu.write(spec + 1, byte);
+        }
+    }
+
+    #[test]
+    fn cgb_bg_attributes() {
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let mut ppu = Ppu::new();
+            ppu.set_renderer(renderer);
+            ppu.set_cgb(true);
+            set_colours(&mut ppu, 0xFF68, 2);
+            let mut vram = vec![0; VRAM_SIZE * 2];
+            // tile 0 in bank 1 has colour 1 in its leftmost pixel, the attributes flip it
+            vram[VRAM_SIZE] = 0x80;
+            vram[VRAM_SIZE + 0x1800] = 2 | tiles::BG_ATTR_BANK | BG_ATTR_X_FLIP;
+            lcd_on(&mut ppu, LCDC_BG_ENABLE | LCDC_TILE_DATA);
+            ppu.tick(DOTS_PER_LINE, &vram, &[0; OAM_SIZE]);
+            assert_eq!((ppu.frame()[0], ppu.frame()[7]), (WHITE, RED), "{renderer:?}");
+        }
+    }
+
+    #[test]
+    fn palette_data_is_out_of_reach_in_mode_3() {
+        let mut ppu = Ppu::new();
+        let (vram, oam) = (vec![0; VRAM_SIZE * 2], [0; OAM_SIZE]);
+        ppu.set_cgb(true);
+        ppu.write(0xFF68, 0x80);
+        lcd_on(&mut ppu, 0);
+        ppu.tick(OAM_SCAN_DOTS, &vram, &oam);
+        ppu.write(0xFF69, 0x00);
+        assert_eq!((ppu.read(0xFF68), ppu.read(0xFF69)), (0xC1, 0xFF));
+        ppu.tick(DRAWING_DOTS, &vram, &oam);
+        ppu.write(0xFF68, 0x00);
+        assert_eq!(ppu.read(0xFF69), 0xFF);
+    }
+
+    fn overlapping_objects(opri: u8) -> u16 {
+        let mut ppu = Ppu::new();
+        ppu.set_cgb(true);
+        ppu.write(0xFF6C, opri);
+        set_colours(&mut ppu, 0xFF6A, 0);
+        let mut vram = vec![0; VRAM_SIZE * 2];
+        vram[16..32].copy_from_slice(&[0xFF, 0x00].repeat(8)); // tile 1 colour 1
+        vram[32..48].copy_from_slice(&[0x00, 0xFF].repeat(8)); // tile 2 colour 2
+        let mut oam = [0; OAM_SIZE];
+        // the first object in OAM sits further right
+        oam[..8].copy_from_slice(&[16, 12, 1, 0, 16, 8, 2, 0]);
+        lcd_on(&mut ppu, LCDC_BG_ENABLE | LCDC_OBJ_ENABLE);
+        ppu.tick(DOTS_PER_LINE, &vram, &oam);
+        ppu.frame()[4]
+    }
+
+    #[test]
+    fn opri_picks_between_oam_order_and_x_order() {
+        assert_eq!(overlapping_objects(0), RED);
+        assert_eq!(overlapping_objects(1), GREEN);
+    }
+}
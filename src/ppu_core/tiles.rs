@@ -0,0 +1,62 @@
+/* Tile data lookups shared by the renderers.
+   A tile is 8x8 pixels, 16 bytes, two bytes per row. The first byte holds bit 0 of each
+   pixel's colour and the second bit 1, leftmost pixel in bit 7.
+
+   LCDC bit 4 picks how the background and window find tile data:
+     1  0x8000 - 0x8FFF, tile numbers 0 to 255
+     0  0x8800 - 0x97FF, tile numbers -128 to 127 around 0x9000
+   Objects always use the 0x8000 way.
+   The tile maps are 32x32 tile numbers at 0x9800 (LCDC bit 3 or 6 clear) or 0x9C00.
+
+   On CGB vram bank 1 holds an attribute byte for every map entry, at the same offset:
+     7 bg over objects, 6 y flip, 5 x flip, 3 tile data from vram bank 1, 2-0 palette
+   The vram slices passed around here hold both banks, bank 1 starts at VRAM_SIZE.
+*/
+use crate::cpu_core::memory::VRAM_SIZE;
+use crate::ppu_core::ppu::LCDC_TILE_DATA;
+
+pub const MAP_SIZE: usize = 32;
+
+pub const BG_ATTR_PRIORITY: u8 = 0x80;
+pub const BG_ATTR_Y_FLIP: u8 = 0x40;
+pub const BG_ATTR_X_FLIP: u8 = 0x20;
+pub const BG_ATTR_BANK: u8 = 0x08;
+pub const BG_ATTR_PALETTE: u8 = 0x07;
+
+// vram offset of the first byte of a background/window tile
+pub fn bg_tile_address(lcdc: u8, tile: u8) -> usize {
+    if lcdc & LCDC_TILE_DATA != 0 {
+        tile as usize * 16
+    } else {
+        (0x1000 + (tile as i8 as i32) * 16) as usize
+    }
+}
+
+// vram offset of the map picked by an LCDC map bit
+pub fn map_address(high_map: bool) -> usize {
+    if high_map { 0x1C00 } else { 0x1800 }
+}
+
+// the two bytes of one row of the tile at address
+pub fn tile_row(vram: &[u8], address: usize, row: usize) -> (u8, u8) {
+    let address = address + row * 2;
+    (vram[address], vram[address + 1])
+}
+
+// a background/window tile row with the bank and y flip from its attributes applied
+pub fn bg_tile_row(vram: &[u8], lcdc: u8, tile: u8, attributes: u8, row: usize) -> (u8, u8) {
+    let row = if attributes & BG_ATTR_Y_FLIP != 0 { 7 - row } else { row };
+    let bank = if attributes & BG_ATTR_BANK != 0 { VRAM_SIZE } else { 0 };
+    tile_row(vram, bank + bg_tile_address(lcdc, tile), row)
+}
+
+// colour number (0-3) of pixel x, counting from the left
+pub fn pixel(row: (u8, u8), x: usize) -> u8 {
+    let bit = 7 - x;
+    (((row.1 >> bit) & 1) << 1) | ((row.0 >> bit) & 1)
+}
+
+// what a DMG palette register turns a colour number into
+pub fn shade(palette: u8, colour: u8) -> u8 {
+    (palette >> (colour * 2)) & 0x03
+}